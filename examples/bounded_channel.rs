@@ -1,7 +1,4 @@
-use core::time::Duration;
-
 use woi::channel::mpsc;
-use woi::time::sleep;
 use woi::Runtime;
 
 fn main() {
@@ -11,21 +8,21 @@ fn main() {
     rt.block_on(async {
         let (tx, rx) = mpsc::bounded::channel(2);
 
-        woi::spawn(async {
-            let tx = tx.clone();
-            tx.send("task 1").await.unwrap();
+        let tx1 = tx.clone();
+        woi::spawn(async move {
+            tx1.send("task 1").await.unwrap();
             println!("Sent message from task 1");
         });
 
-        woi::spawn(async {
-            let tx = tx.clone();
-            tx.send("task 2").await.unwrap();
+        let tx2 = tx.clone();
+        woi::spawn(async move {
+            tx2.send("task 2").await.unwrap();
             println!("Sent message from task 2");
         });
 
-        woi::spawn(async {
-            let tx = tx.clone();
-            tx.send("task 3").await.unwrap();
+        let tx3 = tx.clone();
+        woi::spawn(async move {
+            tx3.send("task 3").await.unwrap();
             println!("Sent message from task 3");
         });
 
@@ -10,8 +10,9 @@ fn main() {
     let rt = Runtime::new();
     rt.block_on(async {
         let (tx, rx) = mpsc::unbounded::channel();
-        woi::spawn(async {
-            let tx = tx.clone();
+        let tx1 = tx.clone();
+        woi::spawn(async move {
+            let tx = tx1;
             println!("Sending message from task 1");
             tx.send("task 1: fly.io").unwrap()
         });
@@ -11,8 +11,9 @@ fn main() {
     rt.block_on(async {
         let (tx, rx) = mpsc::unbounded::channel();
 
-        let h1 = woi::spawn(async {
-            let tx = tx.clone();
+        let tx1 = tx.clone();
+        let h1 = woi::spawn(async move {
+            let tx = tx1;
             println!("Sending message from handle 1");
             tx.send("hello").unwrap()
         });
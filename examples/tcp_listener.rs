@@ -0,0 +1,39 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use woi::io::{AsyncReadExt, AsyncWriteExt};
+use woi::net::TcpListener;
+use woi::Runtime;
+
+fn main() {
+    tracing_subscriber::fmt::init();
+
+    let rt = Runtime::new();
+    rt.block_on(async {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+        let listener = TcpListener::bind(addr).await.unwrap();
+        println!("Listening on {}", listener.local_addr().unwrap());
+
+        loop {
+            let (mut stream, peer) = listener.accept().await.unwrap();
+            println!("Accepted connection from {}", peer);
+
+            woi::spawn(async move {
+                let mut buf = vec![0; 1024];
+                loop {
+                    let n = stream
+                        .read(&mut buf)
+                        .await
+                        .expect("failed to read data from socket");
+                    if n == 0 {
+                        break;
+                    }
+                    stream
+                        .write_all(&buf[..n])
+                        .await
+                        .expect("failed to write data to socket");
+                }
+                println!("Connection from {} closed", peer);
+            });
+        }
+    })
+}
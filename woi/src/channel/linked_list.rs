@@ -7,6 +7,10 @@ pub(crate) struct LinkedList {
     tail: *mut Waiter,
 }
 
+// The list only holds pointers to waiters, which are safe to send. The
+// semaphore owning the list synchronizes access to them
+unsafe impl Send for LinkedList {}
+
 #[allow(unused)]
 impl LinkedList {
     pub fn new() -> LinkedList {
@@ -1,7 +1,7 @@
 //! A bounded multi-producer, single-consumer queue for sending values between
 //! asynchronous tasks.
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 use futures::future::poll_fn;
 
@@ -9,26 +9,26 @@ use super::channel::Channel;
 use crate::channel::error::{SendError, TryRecvError};
 
 pub fn channel<T>(size: usize) -> (Sender<T>, Receiver<T>) {
-    let chan = Rc::new(Channel::new(size));
+    let chan = Arc::new(Channel::new(size));
     (Sender::new(chan.clone()), Receiver::new(chan))
 }
 
 pub struct Permit<T> {
-    chan: Rc<Channel<T>>,
+    chan: Arc<Channel<T>>,
 }
 
 pub struct Sender<T> {
-    chan: Rc<Channel<T>>,
+    chan: Arc<Channel<T>>,
 }
 
 pub struct Receiver<T> {
-    chan: Rc<Channel<T>>,
+    chan: Arc<Channel<T>>,
 }
 
 // ==== impl Sender =====
 
 impl<T> Sender<T> {
-    pub fn new(chan: Rc<Channel<T>>) -> Sender<T> {
+    pub fn new(chan: Arc<Channel<T>>) -> Sender<T> {
         Sender { chan }
     }
 
@@ -74,7 +74,7 @@ impl<T> Drop for Sender<T> {
 // ===== impl Receiver =====
 
 impl<T> Receiver<T> {
-    pub fn new(chan: Rc<Channel<T>>) -> Receiver<T> {
+    pub fn new(chan: Arc<Channel<T>>) -> Receiver<T> {
         Receiver { chan }
     }
 
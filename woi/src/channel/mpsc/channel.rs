@@ -1,13 +1,17 @@
-use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
 use std::task::{Context, Poll, Waker};
 
+use futures::ready;
+
 use crate::channel::error::{SendError, TryRecvError};
-use crate::channel::semaphore::{Acquire, Semaphore};
+use crate::channel::semaphore::Semaphore;
+use crate::task::coop;
 
 pub struct Channel<T> {
-    // Inner state of the channel
-    inner: RefCell<Inner<T>>,
+    // Inner state of the channel. Senders and the receiver may be on
+    // different threads
+    inner: Mutex<Inner<T>>,
     // Controls access to the channel
     semaphore: Semaphore,
 }
@@ -33,7 +37,7 @@ impl<T> Channel<T> {
     pub fn new(size: usize) -> Channel<T> {
         Channel {
             semaphore: Semaphore::new(size),
-            inner: RefCell::new(Inner {
+            inner: Mutex::new(Inner {
                 queue: VecDeque::with_capacity(size),
                 tx_count: 1,
                 state: State::Open,
@@ -42,31 +46,35 @@ impl<T> Channel<T> {
         }
     }
 
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        self.inner.lock().unwrap()
+    }
+
     #[allow(unused)]
     pub fn wake_rx(&self) {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         if let Some(waker) = inner.rx_waker.take() {
             waker.wake();
         }
     }
 
     pub fn close(&self) {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         inner.state = State::Closed;
     }
 
     pub fn incr_tx_count(&self) {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         inner.tx_count += 1;
     }
 
     pub fn decr_tx_count(&self) {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         inner.tx_count -= 1;
     }
 
     pub fn tx_count(&self) -> usize {
-        self.inner.borrow().tx_count
+        self.lock().tx_count
     }
 
     pub fn semaphore(&self) -> &Semaphore {
@@ -74,7 +82,7 @@ impl<T> Channel<T> {
     }
 
     pub fn send(&self, message: T) -> Result<(), SendError<T>> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         match inner.state {
             State::Open => {
                 inner.queue.push_back(message);
@@ -88,7 +96,9 @@ impl<T> Channel<T> {
     }
 
     pub fn recv(&self, cx: &mut Context) -> Poll<Option<T>> {
-        let mut inner = self.inner.borrow_mut();
+        ready!(coop::poll_proceed(cx));
+
+        let mut inner = self.lock();
         match inner.queue.pop_front() {
             // If there is a message, regardless if the channel is closed,
             // we read the message. This allows us to read any outstanding
@@ -120,7 +130,7 @@ impl<T> Channel<T> {
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
         match inner.queue.pop_front() {
             Some(message) => Ok(message),
             None => match inner.state {
@@ -1,7 +1,7 @@
 //! An unbounded multi-producer, single-consumer queue for sending values between
 //! asynchronous tasks.
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 use futures::future::poll_fn;
 
@@ -9,22 +9,22 @@ use super::channel::Channel;
 use crate::channel::error::{SendError, TryRecvError};
 
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let chan = Rc::new(Channel::new(128));
+    let chan = Arc::new(Channel::new(128));
     (Sender::new(chan.clone()), Receiver::new(chan))
 }
 
 pub struct Sender<T> {
-    chan: Rc<Channel<T>>,
+    chan: Arc<Channel<T>>,
 }
 
 pub struct Receiver<T> {
-    chan: Rc<Channel<T>>,
+    chan: Arc<Channel<T>>,
 }
 
 // ==== impl Sender =====
 
 impl<T> Sender<T> {
-    pub fn new(chan: Rc<Channel<T>>) -> Sender<T> {
+    pub fn new(chan: Arc<Channel<T>>) -> Sender<T> {
         Sender { chan }
     }
 
@@ -57,7 +57,7 @@ impl<T> Drop for Sender<T> {
 // ===== impl Receiver =====
 
 impl<T> Receiver<T> {
-    pub fn new(chan: Rc<Channel<T>>) -> Receiver<T> {
+    pub fn new(chan: Arc<Channel<T>>) -> Receiver<T> {
         Receiver { chan }
     }
 
@@ -75,4 +75,4 @@ impl<T> Drop for Receiver<T> {
         tracing::debug!("Dropping receiver");
         self.chan.close();
     }
-}
\ No newline at end of file
+}
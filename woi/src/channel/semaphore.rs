@@ -1,14 +1,22 @@
-use core::cell::{Cell, RefCell};
 use core::future::Future;
 use core::pin::Pin;
 use core::ptr;
 use core::task::{Context, Poll, Waker};
+use std::sync::Mutex;
+
+use futures::ready;
 
 use super::linked_list::LinkedList;
+use crate::task::coop;
 
 pub struct Semaphore {
-    permits: Cell<usize>,
-    waiters: RefCell<LinkedList>,
+    /// Senders on different threads may acquire permits at the same time
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    permits: usize,
+    waiters: LinkedList,
 }
 
 // We have to make this unpin so that we ensure that the waiter isn't
@@ -27,22 +35,29 @@ pub struct Acquire<'a> {
 
 pub struct AcquireError;
 
+// Waiters are only linked into and out of the list while holding the
+// semaphore's lock
+unsafe impl Send for Waiter {}
+unsafe impl Sync for Waiter {}
+
 // ===== impl Semaphore =====
 
 impl Semaphore {
     pub fn new(permits: usize) -> Semaphore {
         Semaphore {
-            permits: Cell::new(permits),
-            waiters: RefCell::new(LinkedList::new()),
+            inner: Mutex::new(Inner {
+                permits,
+                waiters: LinkedList::new(),
+            }),
         }
     }
 
     pub(crate) fn release(&self) {
-        self.permits.set(self.permits.get() + 1);
-        tracing::debug!("Released permit. Available: {}", self.permits.get());
+        let mut inner = self.inner.lock().unwrap();
+        inner.permits += 1;
+        tracing::debug!("Released permit. Available: {}", inner.permits);
 
-        let mut waiters = self.waiters.borrow_mut();
-        if let Some(waiter) = waiters.pop_front() {
+        if let Some(waiter) = inner.waiters.pop_front() {
             // TODO: Drop the waker?
             if let Some(waker) = &waiter.waker {
                 waker.wake_by_ref()
@@ -59,17 +74,19 @@ impl Semaphore {
         cx: &mut Context,
         waiter: &mut Waiter,
     ) -> Poll<Result<(), AcquireError>> {
-        let permits = self.permits.get();
-        if permits > 0 {
-            self.permits.set(permits - 1);
-            tracing::debug!("Acquired permit. Available: {}", self.permits.get());
+        ready!(coop::poll_proceed(cx));
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.permits > 0 {
+            inner.permits -= 1;
+            tracing::debug!("Acquired permit. Available: {}", inner.permits);
             return Poll::Ready(Ok(()));
         }
 
         tracing::debug!("No permits available!");
         waiter.waker = Some(cx.waker().clone());
         let waiter_ptr = waiter as *const _ as *mut Waiter;
-        self.waiters.borrow_mut().push_back(waiter_ptr);
+        inner.waiters.push_back(waiter_ptr);
 
         Poll::Pending
     }
@@ -94,7 +111,7 @@ impl Waiter {
 // ===== impl Acquire =====
 
 impl<'a> Acquire<'a> {
-    pub fn new(semaphore: &'a Semaphore) -> Acquire {
+    pub fn new(semaphore: &'a Semaphore) -> Acquire<'a> {
         Acquire {
             semaphore,
             waiter: Waiter::new(),
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+use futures::ready;
+
+use super::epoll::Interest;
+use super::io_source::Direction;
+use super::pollable::Pollable;
+
+/// Registers an arbitrary file descriptor with the reactor so it can be
+/// waited on for readiness. The file descriptor should be in non-blocking
+/// mode and is expected to be registered by only one `AsyncFd` at a time
+///
+/// The reactor is edge-triggered: once an operation returns
+/// [`io::ErrorKind::WouldBlock`], readiness has to be cleared (using
+/// [`AsyncFdReadyGuard::clear_ready`] or [`AsyncFdReadyGuard::try_io`])
+/// before waiting again, otherwise the wait finishes immediately
+pub struct AsyncFd<T: AsRawFd> {
+    inner: Pollable<T>,
+}
+
+/// Returned by [`AsyncFd::readable`] and [`AsyncFd::writable`] once the
+/// file descriptor is ready in that direction
+pub struct AsyncFdReadyGuard<'a, T: AsRawFd> {
+    fd: &'a AsyncFd<T>,
+    direction: Direction,
+    /// Readiness tick the guard was created at
+    tick: usize,
+}
+
+/// Returned by [`AsyncFdReadyGuard::try_io`] when the operation would have
+/// blocked. Readiness has been cleared so the next wait goes to the reactor
+#[derive(Debug)]
+pub struct TryIoError(());
+
+// ===== impl AsyncFd =====
+
+impl<T: AsRawFd> AsyncFd<T> {
+    /// Registers the file descriptor for both reading and writing
+    pub fn new(io: T) -> io::Result<AsyncFd<T>> {
+        AsyncFd::with_interest(io, Interest::READABLE | Interest::WRITABLE)
+    }
+
+    /// Registers the file descriptor for the given interest only. Waiting
+    /// in a direction that was not registered never completes
+    pub fn with_interest(io: T, interest: Interest) -> io::Result<AsyncFd<T>> {
+        let inner = Pollable::new_with_interest(io, interest)?;
+        Ok(AsyncFd { inner })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    /// Deregisters the file descriptor from the reactor and returns the
+    /// inner value without closing it
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Waits for the file descriptor to become readable
+    pub async fn readable(&self) -> io::Result<AsyncFdReadyGuard<'_, T>> {
+        poll_fn(|cx| self.poll_read_ready(cx)).await
+    }
+
+    /// Waits for the file descriptor to become writable
+    pub async fn writable(&self) -> io::Result<AsyncFdReadyGuard<'_, T>> {
+        poll_fn(|cx| self.poll_write_ready(cx)).await
+    }
+
+    pub fn poll_read_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<AsyncFdReadyGuard<'_, T>>> {
+        let tick = ready!(self.inner.poll_ready(Direction::Read, cx))?;
+        Poll::Ready(Ok(AsyncFdReadyGuard {
+            fd: self,
+            direction: Direction::Read,
+            tick,
+        }))
+    }
+
+    pub fn poll_write_ready(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<AsyncFdReadyGuard<'_, T>>> {
+        let tick = ready!(self.inner.poll_ready(Direction::Write, cx))?;
+        Poll::Ready(Ok(AsyncFdReadyGuard {
+            fd: self,
+            direction: Direction::Write,
+            tick,
+        }))
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for AsyncFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+// ===== impl AsyncFdReadyGuard =====
+
+impl<'a, T: AsRawFd> AsyncFdReadyGuard<'a, T> {
+    pub fn get_ref(&self) -> &'a AsyncFd<T> {
+        self.fd
+    }
+
+    /// Marks the file descriptor as no longer ready in this direction. Call
+    /// it after an operation returned [`io::ErrorKind::WouldBlock`]
+    pub fn clear_ready(&mut self) {
+        self.fd.inner.clear_readiness(self.direction, self.tick)
+    }
+
+    /// Runs the operation, clearing readiness if it would block
+    pub fn try_io<R>(
+        &mut self,
+        f: impl FnOnce(&'a AsyncFd<T>) -> io::Result<R>,
+    ) -> Result<io::Result<R>, TryIoError> {
+        match f(self.fd) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.clear_ready();
+                Err(TryIoError(()))
+            }
+            res => Ok(res),
+        }
+    }
+}
+
+// ===== impl TryIoError =====
+
+impl Error for TryIoError {}
+
+impl fmt::Display for TryIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "operation would block")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn read_when_ready() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (a, mut b) = UnixStream::pair().unwrap();
+            a.set_nonblocking(true).unwrap();
+            let fd = AsyncFd::with_interest(a, Interest::READABLE).unwrap();
+
+            b.write_all(b"hello").unwrap();
+
+            let mut buf = [0; 5];
+            let n = loop {
+                let mut guard = fd.readable().await.unwrap();
+                match guard.try_io(|fd| fd.get_ref().read(&mut buf)) {
+                    Ok(res) => break res.unwrap(),
+                    Err(_) => continue,
+                }
+            };
+            assert_eq!(&buf[..n], b"hello");
+
+            // Nothing left to read, so readiness gets cleared
+            let mut guard = fd.readable().await.unwrap();
+            assert!(guard.try_io(|fd| fd.get_ref().read(&mut buf)).is_err());
+
+            // Registering again fails unless it was deregistered
+            let stream = fd.into_inner();
+            AsyncFd::new(stream).unwrap();
+        });
+    }
+}
@@ -7,15 +7,16 @@
 
 use std::fmt::Display;
 use std::io;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::time::Duration;
 
 use bitflags::bitflags;
 use libc;
 
-/// Provides functionality for interacting epoll.
+/// Provides functionality for interacting epoll. The epoll instance is
+/// closed when dropped
 pub struct Epoll {
-    pub fd: RawFd,
+    fd: OwnedFd,
 }
 
 /// An equivalent of `libc::epoll_data`
@@ -38,6 +39,7 @@ pub struct Event {
 pub type Events = Vec<Event>;
 
 bitflags! {
+    /// The readiness events an IO resource is registered for
     pub struct Interest: u32 {
         const READABLE       = (libc::EPOLLET  | libc::EPOLLIN | libc::EPOLLRDHUP) as u32;
         const WRITABLE       = (libc::EPOLLET  | libc::EPOLLOUT) as u32;
@@ -93,26 +95,27 @@ impl<T: AsRawFd> Source for &T {
 
 impl Epoll {
     pub fn new() -> io::Result<Epoll> {
-        let fd = epoll::create()?;
+        let fd = sys::create()?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
         let poll = Epoll { fd };
         Ok(poll)
     }
 
     pub fn add(&self, source: impl Source, interest: Interest, token: Token) -> io::Result<()> {
         let event = Event::new(interest, token);
-        epoll::ctl(self.fd, CtlOp::Add, source.raw_fd(), Some(event))?;
+        sys::ctl(self.as_raw_fd(), CtlOp::Add, source.raw_fd(), Some(event))?;
         Ok(())
     }
 
     pub fn delete(&self, source: impl Source) -> io::Result<()> {
-        epoll::ctl(self.fd, CtlOp::Del, source.raw_fd(), None)?;
+        sys::ctl(self.as_raw_fd(), CtlOp::Del, source.raw_fd(), None)?;
         Ok(())
     }
 
     #[allow(unused)]
     pub fn modify(&self, source: impl Source, interest: Interest, token: Token) -> io::Result<()> {
         let event = Event::new(interest, token);
-        epoll::ctl(self.fd, CtlOp::Mod, source.raw_fd(), Some(event))?;
+        sys::ctl(self.as_raw_fd(), CtlOp::Mod, source.raw_fd(), Some(event))?;
         Ok(())
     }
 
@@ -120,28 +123,27 @@ impl Epoll {
         events.clear();
 
         let timeout = match timeout {
-            Some(duration) => duration.as_millis() as i32,
+            // Rounded up so we don't wake just before a deadline and spin
+            Some(duration) => duration
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(i32::MAX as u128) as i32,
             None => -1, // TThis blocks indefinitely
         };
-        let n_events = epoll::wait(self.fd, events, timeout)?;
+        let n_events = sys::wait(self.as_raw_fd(), events, timeout)?;
         tracing::debug!("Epoll: Received {} events", n_events);
 
-        // This is actually safe to call because `epoll::wait` returns the
+        // This is actually safe to call because `sys::wait` returns the
         // number of events that were returned. Got this from Mio:
         // https://github.com/tokio-rs/mio/blob/22e885859bb481ae4c2827ab48552c3159fcc7f8/src/sys/unix/selector/epoll.rs#L77
         unsafe { events.set_len(n_events as usize) };
         Ok(())
     }
-
-    pub fn close(&self) -> io::Result<()> {
-        epoll::close(self.fd)
-    }
 }
 
-impl Drop for Epoll {
-    fn drop(&mut self) {
-        tracing::debug!("Drop: epoll_fd={}", self.fd);
-        let _ = self.close();
+impl AsRawFd for Epoll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
     }
 }
 
@@ -183,18 +185,23 @@ impl Event {
         epollin || epollpri || epollhup || epollrdhup
     }
 
+    // EPOLLERR is reported when the socket has a pending error, such as a
+    // failed non-blocking connect. We treat it as writable so the writer
+    // wakes up and discovers the error
     pub fn is_writable(&self) -> bool {
         let interest = self.interest as libc::c_int;
 
         let epollout = interest & libc::EPOLLOUT == libc::EPOLLOUT;
         let epollhup = interest & libc::EPOLLHUP == libc::EPOLLHUP;
+        let epollerr = interest & libc::EPOLLERR == libc::EPOLLERR;
 
-        epollout || epollhup
+        epollout || epollhup || epollerr
     }
 
     pub(crate) fn interest(&self) -> Interest {
-        // Gauranteed to never panic so can unwrap
-        Interest::from_bits(self.interest).unwrap()
+        // Events carry bits we never register for (EPOLLERR, EPOLLHUP) so
+        // validating against our flags would fail. Keep them all instead
+        unsafe { Interest::from_bits_unchecked(self.interest) }
     }
 }
 
@@ -222,7 +229,7 @@ impl Display for Interest {
 
 // For documentation of the various calls, refer to the
 // [epoll man pages](https://man7.org/linux/man-pages/man7/epoll.7.html)
-mod epoll {
+mod sys {
     use super::{CtlOp, Event, Events};
     use std::io;
     use std::os::unix::prelude::RawFd;
@@ -262,13 +269,6 @@ mod epoll {
         cvt(unsafe { libc::epoll_wait(epfd, events, capacity, timeout) })
     }
 
-    // Safe wrapper around `libc::close`
-    #[cfg(target_os = "linux")]
-    pub(super) fn close(fd: RawFd) -> io::Result<()> {
-        cvt(unsafe { libc::close(fd) })?;
-        Ok(())
-    }
-
     // Converts C error codes into a Rust Result type
     fn cvt(result: i32) -> io::Result<i32> {
         if result < 0 {
@@ -288,9 +288,7 @@ mod tests {
         // Test it works by creating an instance of epoll and then closing it
         // If this function does not work, it will panic
         let epoll = Epoll::new().unwrap();
-        // Drop the error so we have a gaurantee that if the test fails, it is from
-        // creating the epoll instance. This is arguably shady
-        let _ = epoll.close();
+        drop(epoll);
     }
 
     #[test]
@@ -303,7 +301,6 @@ mod tests {
         let listener = TcpListener::bind("localhost:3000").unwrap();
 
         epoll.add(listener.as_raw_fd(), interest, Token(1)).unwrap();
-        let _ = epoll.close();
     }
 
     #[test]
@@ -325,7 +322,6 @@ mod tests {
         let maxevents = 10;
         let mut events = Events::with_capacity(maxevents);
         epoll.poll(&mut events, None).unwrap();
-        epoll.close().unwrap();
 
         assert_eq!(events.len(), 1);
     }
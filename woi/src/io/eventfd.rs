@@ -0,0 +1,71 @@
+use std::io;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// A counter living in the kernel which becomes readable once it is
+/// incremented. It lets other threads notify the reactor
+pub(crate) struct EventFd {
+    fd: OwnedFd,
+}
+
+impl EventFd {
+    pub fn new() -> io::Result<EventFd> {
+        let flags = libc::EFD_NONBLOCK | libc::EFD_CLOEXEC;
+        let fd = cvt(unsafe { libc::eventfd(0, flags) })?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(EventFd { fd })
+    }
+
+    /// Increments the counter, making the eventfd readable
+    pub fn notify(&self) -> io::Result<()> {
+        let buf = 1u64.to_ne_bytes();
+        let res = unsafe { libc::write(self.as_raw_fd(), buf.as_ptr() as *const _, buf.len()) };
+        match res {
+            // The counter is full, meaning it is already readable
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock => Ok(()),
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resets the counter to zero, returning how many times it was notified
+    pub fn reset(&self) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        let res = unsafe { libc::read(self.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+        match res {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock => Ok(0),
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(u64::from_ne_bytes(buf)),
+        }
+    }
+}
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+// Converts C error codes into a Rust Result type
+fn cvt(result: i32) -> io::Result<i32> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_reset() {
+        let eventfd = EventFd::new().unwrap();
+        assert_eq!(eventfd.reset().unwrap(), 0);
+
+        eventfd.notify().unwrap();
+        eventfd.notify().unwrap();
+        assert_eq!(eventfd.reset().unwrap(), 2);
+        assert_eq!(eventfd.reset().unwrap(), 0);
+    }
+}
@@ -1,20 +1,21 @@
-use std::cell::RefCell;
 use std::io;
 use std::os::unix::prelude::RawFd;
+use std::sync::{Mutex, MutexGuard};
 use std::task::{Context, Poll, Waker};
 
 use super::epoll::{Event, Token};
 use super::readiness::Readiness;
 
-#[derive(Clone, Default)]
+#[derive(Default)]
 pub(crate) struct IoSource {
     /// Raw file descriptor of the IO resource
     pub(crate) io: RawFd,
     /// Token tying io source to slot in reactor slab
     pub(crate) token: Token,
     /// Holds state on an io resource's readiness for
-    /// reading and writing
-    pub(crate) inner: RefCell<Inner>,
+    /// reading and writing. Events are dispatched by whichever thread
+    /// is parked on the reactor
+    pub(crate) inner: Mutex<Inner>,
 }
 
 #[derive(Clone, Default)]
@@ -22,10 +23,16 @@ pub(crate) struct Inner {
     /// Readiness of the source. Used to determine whether
     /// the source is ready for reading, writing or both
     pub(crate) readiness: Readiness,
+    /// Bumped every time readiness is set by the reactor. Lets us tell
+    /// whether an event arrived while an operation was in progress
+    pub(crate) tick: usize,
     /// Waker registered by poll_readable
     pub(crate) reader: Option<Waker>,
     /// Waker registered by poll_writable
     pub(crate) writer: Option<Waker>,
+    /// Set once the reactor has shut down. The source won't receive any
+    /// more events
+    pub(crate) shutdown: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -35,15 +42,27 @@ pub(crate) enum Direction {
 }
 
 impl IoSource {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // Nothing panics while holding the lock, so it can't be poisoned
+        self.inner.lock().unwrap()
+    }
+
     /// Set the readiness of the task (readable, writable or both)
     pub fn set_readiness(&self, event: &Event) {
-        let mut inner = self.inner.borrow_mut();
-        inner.readiness = Readiness::from_event(event)
+        let mut inner = self.lock();
+        inner.readiness = Readiness::from_event(event);
+        inner.tick = inner.tick.wrapping_add(1);
     }
 
-    /// Unset the bit indicating readiness for a specific [`Direction`]
-    pub fn clear_readiness(&self, direction: Direction) {
-        let mut inner = self.inner.borrow_mut();
+    /// Unset the bit indicating readiness for a specific [`Direction`]. The
+    /// tick is the one returned by [`poll_ready`](Self::poll_ready). If
+    /// the reactor has set readiness since, it is left alone since the
+    /// resource may have become ready again after the operation failed
+    pub fn clear_readiness(&self, direction: Direction, tick: usize) {
+        let mut inner = self.lock();
+        if inner.tick != tick {
+            return;
+        }
         match direction {
             Direction::Read => inner.readiness = inner.readiness - Readiness::READABLE,
             Direction::Write => inner.readiness = inner.readiness - Readiness::WRITABLE,
@@ -57,7 +76,7 @@ impl IoSource {
     pub fn wake(&self, event: &Event) {
         let mut wakers = Vec::new();
 
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.lock();
 
         if event.is_readable() {
             if let Some(waker) = inner.reader.take() {
@@ -71,6 +90,27 @@ impl IoSource {
             }
         }
 
+        // Wake outside of the lock
+        drop(inner);
+
+        for waker in wakers {
+            waker.wake()
+        }
+    }
+
+    /// Called when the reactor shuts down. Any task waiting on the source
+    /// is woken and from then on its IO fails
+    pub fn shutdown(&self) {
+        let mut inner = self.lock();
+        inner.shutdown = true;
+        let wakers: Vec<_> = inner
+            .reader
+            .take()
+            .into_iter()
+            .chain(inner.writer.take())
+            .collect();
+        drop(inner);
+
         for waker in wakers {
             waker.wake()
         }
@@ -78,26 +118,28 @@ impl IoSource {
 
     /// Determines whether the IO resource is ready to be polled for
     /// either reading or writing. In the event it is not ready, a
-    /// waker is registered in the specified direction (read/write)
+    /// waker is registered in the specified direction (read/write).
+    /// Returns the readiness tick, which is passed on to
+    /// [`clear_readiness`](Self::clear_readiness)
     pub(crate) fn poll_ready(
         &self,
         direction: Direction,
         cx: &mut Context<'_>,
-    ) -> Poll<io::Result<()>> {
-        match direction {
-            Direction::Read => {
-                if self.readable() {
-                    return Poll::Ready(Ok(()));
-                }
-            }
-            Direction::Write => {
-                if self.writable() {
-                    return Poll::Ready(Ok(()));
-                }
-            }
+    ) -> Poll<io::Result<usize>> {
+        // Readiness is checked under the same lock the waker is registered
+        // with, so an event arriving on another thread in between isn't lost
+        let mut inner = self.lock();
+        if inner.shutdown {
+            return Poll::Ready(Err(io::Error::other("the reactor has shut down")));
         }
 
-        let mut inner = self.inner.borrow_mut();
+        let ready = match direction {
+            Direction::Read => Readiness::READABLE,
+            Direction::Write => Readiness::WRITABLE,
+        };
+        if inner.readiness & ready == ready {
+            return Poll::Ready(Ok(inner.tick));
+        }
 
         let slot = match direction {
             Direction::Read => &mut inner.reader,
@@ -111,35 +153,4 @@ impl IoSource {
 
         Poll::Pending
     }
-
-    /// Determines whether the IO resource is ready for reading. Just
-    /// forwards to [`poll_ready`] with a read [`Direction`]
-    pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        tracing::debug!("Invoking poll_readable");
-        let res = self.poll_ready(Direction::Read, cx);
-        match res {
-            Poll::Ready(Ok(())) => tracing::debug!("poll_readable returned Poll::Ready(ok)"),
-            Poll::Ready(Err(_)) => tracing::debug!("poll_readable returned Poll::Ready(err)"),
-            Poll::Pending => tracing::debug!("poll_readable returned Poll::Pending"),
-        }
-        res
-    }
-
-    /// Determines whether the IO resource is ready for reading. Just
-    /// forwards to [`poll_ready`] with a write [`Direction`]
-    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        self.poll_ready(Direction::Write, cx)
-    }
-
-    /// Is the IO resource readable?
-    pub fn readable(&self) -> bool {
-        let inner = self.inner.borrow();
-        inner.readiness & Readiness::READABLE == Readiness::READABLE
-    }
-
-    /// Is the IO resource writable?
-    pub fn writable(&self) -> bool {
-        let inner = self.inner.borrow();
-        inner.readiness & Readiness::WRITABLE == Readiness::WRITABLE
-    }
 }
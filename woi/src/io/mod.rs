@@ -1,4 +1,10 @@
+mod async_fd;
+pub use async_fd::{AsyncFd, AsyncFdReadyGuard, TryIoError};
+
 pub(crate) mod epoll;
+pub use epoll::Interest;
+
+pub(crate) mod eventfd;
 pub(crate) mod io_source;
 pub(crate) mod pollable;
 pub(crate) mod reactor;
@@ -1,6 +1,8 @@
 use std::io::{self, Read, Write};
+use std::mem::ManuallyDrop;
 use std::os::unix::prelude::AsRawFd;
-use std::rc::Rc;
+use std::ptr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use futures::ready;
@@ -8,13 +10,15 @@ use futures::ready;
 use super::epoll::Interest;
 use super::io_source::{Direction, IoSource};
 use super::reactor::Handle;
+use crate::runtime::context;
+use crate::task::coop;
 
 /// Bridges the event queue and IO resources
 pub(crate) struct Pollable<T> {
     /// The IO resource
     io: T,
     /// Stores
-    source: Rc<IoSource>,
+    source: Arc<IoSource>,
     /// Handle to the reactor
     handle: Handle,
 }
@@ -27,17 +31,71 @@ impl<T> Pollable<T> {
     pub fn get_mut(&mut self) -> &mut T {
         &mut self.io
     }
+
+    /// Deregisters the IO resource from the reactor and hands it back
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        let _ = this.handle.inner().deregister(this.source.token);
+
+        // SAFETY: `this` is never used again or dropped, so each field is
+        // moved out exactly once
+        unsafe {
+            drop(ptr::read(&this.source));
+            drop(ptr::read(&this.handle));
+            ptr::read(&this.io)
+        }
+    }
 }
 
 // impl<T> Unpin for Pollable<T> {}
 
 impl<T> Pollable<T> {
-    pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        self.source.poll_readable(cx)
+    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_ready(Direction::Write, cx).map_ok(|_| ())
     }
 
-    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        self.source.poll_writable(cx)
+    /// Waits for the IO resource to be ready in the given [`Direction`].
+    /// Returns the readiness tick to pass to
+    /// [`clear_readiness`](Self::clear_readiness). Takes one operation from
+    /// the task's budget
+    pub fn poll_ready(
+        &self,
+        direction: Direction,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx));
+        self.source.poll_ready(direction, cx)
+    }
+
+    /// Unsets readiness in the given [`Direction`] so the next poll waits
+    /// on the reactor, unless an event arrived since the tick was returned
+    pub fn clear_readiness(&self, direction: Direction, tick: usize) {
+        self.source.clear_readiness(direction, tick)
+    }
+
+    /// Performs a non-blocking operation on the IO resource once it is ready
+    /// in the given [`Direction`]. If the operation would block, readiness is
+    /// cleared and we wait on the reactor before trying again. The whole
+    /// operation takes one operation from the task's budget, however many
+    /// times it is retried
+    pub fn poll_io<R>(
+        &self,
+        direction: Direction,
+        cx: &mut Context<'_>,
+        mut op: impl FnMut(&T) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        ready!(coop::poll_proceed(cx));
+        loop {
+            let tick = ready!(self.source.poll_ready(direction, cx))?;
+
+            match op(&self.io) {
+                Ok(res) => return Poll::Ready(Ok(res)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.source.clear_readiness(direction, tick)
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
     }
 }
 
@@ -48,6 +106,15 @@ impl<T: AsRawFd> Pollable<T> {
     }
 
     pub fn new_with_interest(io: T, interest: Interest) -> io::Result<Self> {
+        if !context::io_enabled() {
+            return Err(io::Error::other("IO is disabled on this runtime"));
+        }
+        Self::register(io, interest)
+    }
+
+    /// Registers the resource even if IO is disabled on the runtime. Timers
+    /// are driven by the reactor either way
+    pub fn register(io: T, interest: Interest) -> io::Result<Self> {
         let handle = Handle::current();
         let source = handle.inner.register(io.as_raw_fd(), interest)?;
         Ok(Pollable { io, source, handle })
@@ -56,14 +123,15 @@ impl<T: AsRawFd> Pollable<T> {
 
 impl<T: Read> Pollable<T> {
     pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx));
         loop {
-            ready!(self.poll_readable(cx))?;
+            let tick = ready!(self.source.poll_ready(Direction::Read, cx))?;
 
             match self.get_mut().read(buf) {
                 Ok(n) => return Poll::Ready(Ok(n)),
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
                     // Clear readiness for the specific direction
-                    self.source.clear_readiness(Direction::Read)
+                    self.source.clear_readiness(Direction::Read, tick)
                 }
                 Err(e) => return Poll::Ready(Err(e)),
             }
@@ -73,14 +141,15 @@ impl<T: Read> Pollable<T> {
 
 impl<T: Write> Pollable<T> {
     pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx));
         loop {
-            ready!(self.poll_writable(cx))?;
+            let tick = ready!(self.source.poll_ready(Direction::Write, cx))?;
 
             match self.get_mut().write(buf) {
                 Ok(n) => return Poll::Ready(Ok(n)),
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
                     // Clear readiness for the specific direction
-                    self.source.clear_readiness(Direction::Write)
+                    self.source.clear_readiness(Direction::Write, tick)
                 }
                 Err(e) => return Poll::Ready(Err(e)),
             }
@@ -1,14 +1,19 @@
-use std::cell::RefCell;
 use std::io;
 use std::os::unix::prelude::RawFd;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use slab::Slab;
 
 use super::epoll::{Epoll, Events, Interest, Token};
+use super::eventfd::EventFd;
 use super::io_source::IoSource;
 
+/// Token of the eventfd used to unpark the reactor. Sources are keyed by
+/// their slot in the slab, which never gets this large
+const UNPARK: Token = Token(usize::MAX);
+
 /// The reactor
 ///
 /// It contains the event queue (epoll) and a list of all IO
@@ -18,29 +23,43 @@ pub(crate) struct Reactor {
     /// Collection of events. Used across calls to [`Epoll::poll`]
     events: Events,
     /// Shared state between the reactor and its handle
-    inner: Rc<Inner>,
+    inner: Arc<Inner>,
 }
 
 /// Handle to the reactor
 #[derive(Clone)]
 pub(crate) struct Handle {
-    pub inner: Rc<Inner>,
+    pub inner: Arc<Inner>,
 }
 
 pub(crate) struct Inner {
     /// The event queue
     pub poll: Epoll,
     /// Collection of IO resources registered in the event queue
-    pub sources: RefCell<Slab<Rc<IoSource>>>,
+    pub sources: Mutex<Slab<Arc<IoSource>>>,
+    /// Registered in the event queue so another thread can interrupt a
+    /// thread parked on the reactor
+    unpark: EventFd,
+    /// Set once the runtime has shut down. Only changed while holding the
+    /// lock on `sources`
+    shutdown: AtomicBool,
 }
 
 impl Reactor {
-    pub fn new() -> io::Result<Reactor> {
+    /// Creates a reactor that takes up to `event_capacity` events from
+    /// epoll at a time
+    pub fn new(event_capacity: usize) -> io::Result<Reactor> {
+        let poll = Epoll::new()?;
+        let unpark = EventFd::new()?;
+        poll.add(&unpark, Interest::READABLE, UNPARK)?;
+
         Ok(Reactor {
-            events: Events::with_capacity(1024),
-            inner: Rc::new(Inner {
-                poll: Epoll::new()?,
-                sources: RefCell::new(Slab::new()),
+            events: Events::with_capacity(event_capacity),
+            inner: Arc::new(Inner {
+                poll,
+                sources: Mutex::new(Slab::new()),
+                unpark,
+                shutdown: AtomicBool::new(false),
             }),
         })
     }
@@ -51,8 +70,8 @@ impl Reactor {
         }
     }
 
-    // Process new events
-    pub fn react(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+    // Process new events. Returns the number of events received
+    pub fn react(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
         self.inner.poll.poll(&mut self.events, timeout)?;
 
         for event in self.events.iter() {
@@ -63,13 +82,38 @@ impl Reactor {
             );
 
             let token = event.token();
-            if let Some(io_source) = self.inner.sources.borrow().get(token.0) {
+            if token == UNPARK {
+                self.inner.unpark.reset()?;
+                continue;
+            }
+
+            // The lock is released before waking so wakers are free to
+            // register or deregister sources
+            let io_source = self.inner.sources.lock().unwrap().get(token.0).cloned();
+            if let Some(io_source) = io_source {
                 io_source.set_readiness(event);
                 io_source.wake(event)
             }
         }
 
-        Ok(())
+        Ok(self.events.len())
+    }
+
+    /// Deregisters every source that is still around. Tasks waiting on
+    /// them are woken and their IO fails from then on, as does registering
+    /// new sources
+    pub fn shutdown(&self) {
+        let sources: Vec<_> = {
+            let mut sources = self.inner.sources.lock().unwrap();
+            self.inner.shutdown.store(true, Ordering::Release);
+            sources.drain().collect()
+        };
+
+        tracing::debug!("Reactor: shutting down {} sources", sources.len());
+        for source in sources {
+            let _ = self.inner.poll.delete(source.io);
+            source.shutdown();
+        }
     }
 }
 
@@ -80,22 +124,33 @@ impl Handle {
         crate::runtime::context::io()
     }
 
-    pub fn inner(&self) -> Rc<Inner> {
+    pub fn inner(&self) -> Arc<Inner> {
         self.inner.clone()
     }
+
+    /// Wakes up the thread parked on the reactor, or makes the next park
+    /// return straight away if no thread is parked
+    pub fn unpark(&self) {
+        // The counter can only fail to be incremented if it is full, in
+        // which case the reactor is already due to wake up
+        let _ = self.inner.unpark.notify();
+    }
 }
 
 // ==== impl Inner =====
 
 impl Inner {
-    pub fn register(&self, io: RawFd, interest: Interest) -> io::Result<Rc<IoSource>> {
+    pub fn register(&self, io: RawFd, interest: Interest) -> io::Result<Arc<IoSource>> {
         tracing::debug!("Registering task in epoll");
 
-        let mut sources = self.sources.borrow_mut();
+        let mut sources = self.sources.lock().unwrap();
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(io::Error::other("the reactor has shut down"));
+        }
         let entry = sources.vacant_entry();
 
         let token = Token(entry.key());
-        let io_source = Rc::new(IoSource {
+        let io_source = Arc::new(IoSource {
             io,
             token,
             ..Default::default()
@@ -110,7 +165,10 @@ impl Inner {
 
     pub fn deregister(&self, token: Token) -> io::Result<()> {
         tracing::debug!("Deregistering task from epoll");
-        let source = self.sources.borrow_mut().remove(token.0);
-        self.poll.delete(source.io)
+        // The source is already gone if the reactor has shut down
+        match self.sources.lock().unwrap().try_remove(token.0) {
+            Some(source) => self.poll.delete(source.io),
+            None => Ok(()),
+        }
     }
 }
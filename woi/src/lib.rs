@@ -3,11 +3,14 @@ pub mod io;
 pub mod net;
 pub mod time;
 
-mod runtime;
+pub mod runtime;
 pub use runtime::Runtime;
 
-mod task;
-pub use task::spawn;
+pub mod task;
+pub use task::{spawn, spawn_local, JoinHandle};
+
+#[cfg(test)]
+mod test_util;
 
 // Re-exports
 pub use futures::join;
@@ -1,20 +1,26 @@
 use std::future::Future;
 use std::io;
-use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-// Async version of ToSocketAddrs trait
-// TODO: Implement blocking APIs for converting strings into
-// SocketAddrs.
+use crate::runtime::context;
+use crate::task::JoinHandle;
+
+// Async version of ToSocketAddrs trait. Literal addresses resolve
+// immediately while host names are looked up on the blocking pool
+// so they don't stall the executor
 pub trait ToSocketAddrs {
     type Iter: Iterator<Item = SocketAddr>;
 
     fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter>;
 }
 
-pub enum ToSocketAddrsFuture<I> {
-    // Resolving
+/// Future returned by [`ToSocketAddrs::to_socket_addrs`]
+pub struct ToSocketAddrsFuture<I>(State<I>);
+
+enum State<I> {
+    Resolving(JoinHandle<io::Result<I>>),
     Ready(io::Result<I>),
     Done,
 }
@@ -24,20 +30,50 @@ pub enum ToSocketAddrsFuture<I> {
 // https://doc.rust-lang.org/nightly/std/pin/index.html#projections-and-structural-pinning
 impl<I> Unpin for ToSocketAddrsFuture<I> {}
 
+impl<I> ToSocketAddrsFuture<I> {
+    fn ready(res: io::Result<I>) -> ToSocketAddrsFuture<I> {
+        ToSocketAddrsFuture(State::Ready(res))
+    }
+}
+
 impl<I: Iterator<Item = SocketAddr>> Future for ToSocketAddrsFuture<I> {
     type Output = io::Result<I>;
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         use std::mem;
-        let state = mem::replace(&mut *self, ToSocketAddrsFuture::Done);
+        let state = mem::replace(&mut self.0, State::Done);
 
         match state {
-            ToSocketAddrsFuture::Ready(res) => Poll::Ready(res),
-            ToSocketAddrsFuture::Done => panic!("Polled completed future"),
+            State::Resolving(mut handle) => match Pin::new(&mut handle).poll(cx) {
+                Poll::Ready(Ok(res)) => Poll::Ready(res),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(io::Error::other(format!(
+                    "host name lookup failed: {}",
+                    e
+                )))),
+                Poll::Pending => {
+                    self.0 = State::Resolving(handle);
+                    Poll::Pending
+                }
+            },
+            State::Ready(res) => Poll::Ready(res),
+            State::Done => panic!("Polled completed future"),
         }
     }
 }
 
+/// Looks the host up on the blocking pool
+fn resolve(host: String) -> ToSocketAddrsFuture<std::vec::IntoIter<SocketAddr>> {
+    let handle = context::blocking().spawn_task(move || {
+        let addrs = std::net::ToSocketAddrs::to_socket_addrs(&host)?;
+        Ok(addrs.collect::<Vec<_>>().into_iter())
+    });
+
+    match handle {
+        Ok(handle) => ToSocketAddrsFuture(State::Resolving(handle)),
+        Err(e) => ToSocketAddrsFuture::ready(Err(e)),
+    }
+}
+
 // ===== impl ToSocketAddrs for SocketAddr[V4/V6]
 
 impl ToSocketAddrs for SocketAddr {
@@ -45,7 +81,7 @@ impl ToSocketAddrs for SocketAddr {
 
     fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
         let iter = Some(*self).into_iter();
-        ToSocketAddrsFuture::Ready(Ok(iter))
+        ToSocketAddrsFuture::ready(Ok(iter))
     }
 }
 
@@ -66,3 +102,122 @@ impl ToSocketAddrs for SocketAddrV6 {
         ToSocketAddrs::to_socket_addrs(&addr)
     }
 }
+
+// ===== impl ToSocketAddrs for (Ip, port)
+
+impl ToSocketAddrs for (IpAddr, u16) {
+    type Iter = std::option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        let addr = SocketAddr::new(self.0, self.1);
+        ToSocketAddrs::to_socket_addrs(&addr)
+    }
+}
+
+impl ToSocketAddrs for (Ipv4Addr, u16) {
+    type Iter = std::option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        let addr = SocketAddrV4::new(self.0, self.1);
+        ToSocketAddrs::to_socket_addrs(&addr)
+    }
+}
+
+impl ToSocketAddrs for (Ipv6Addr, u16) {
+    type Iter = std::option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        let addr = SocketAddrV6::new(self.0, self.1, 0, 0);
+        ToSocketAddrs::to_socket_addrs(&addr)
+    }
+}
+
+// ===== impl ToSocketAddrs for host names
+
+impl ToSocketAddrs for (&str, u16) {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        let (host, port) = *self;
+
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            let addr = SocketAddr::new(ip, port);
+            return ToSocketAddrsFuture::ready(Ok(vec![addr].into_iter()));
+        }
+
+        resolve(format!("{}:{}", host, port))
+    }
+}
+
+impl ToSocketAddrs for (String, u16) {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        ToSocketAddrs::to_socket_addrs(&(self.0.as_str(), self.1))
+    }
+}
+
+impl ToSocketAddrs for str {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        if let Ok(addr) = self.parse::<SocketAddr>() {
+            return ToSocketAddrsFuture::ready(Ok(vec![addr].into_iter()));
+        }
+
+        resolve(self.to_string())
+    }
+}
+
+impl ToSocketAddrs for String {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        ToSocketAddrs::to_socket_addrs(self.as_str())
+    }
+}
+
+// ===== impl ToSocketAddrs for collections and references
+
+impl<'a> ToSocketAddrs for &'a [SocketAddr] {
+    type Iter = std::iter::Cloned<std::slice::Iter<'a, SocketAddr>>;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        ToSocketAddrsFuture::ready(Ok(self.iter().cloned()))
+    }
+}
+
+impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {
+    type Iter = T::Iter;
+
+    fn to_socket_addrs(&self) -> ToSocketAddrsFuture<Self::Iter> {
+        (**self).to_socket_addrs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn resolve_literal() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let addrs: Vec<_> = "127.0.0.1:80".to_socket_addrs().await.unwrap().collect();
+            assert_eq!(addrs, vec![SocketAddr::from(([127, 0, 0, 1], 80))]);
+
+            let addrs: Vec<_> = ("::1", 80).to_socket_addrs().await.unwrap().collect();
+            assert_eq!(addrs, vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 80))]);
+        });
+    }
+
+    #[test]
+    fn resolve_host_name() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let mut addrs = "localhost:80".to_socket_addrs().await.unwrap();
+            assert!(addrs.all(|addr| addr.ip().is_loopback() && addr.port() == 80));
+        });
+    }
+}
@@ -1,7 +1,18 @@
 mod addr;
+pub use addr::{ToSocketAddrs, ToSocketAddrsFuture};
 
-mod tcp;
-pub use tcp::TcpStream;
+mod socket;
+
+pub mod tcp;
+pub use tcp::{
+    ConnectError, Incoming, TcpListener, TcpSocket, TcpStream, CONNECTION_ATTEMPT_DELAY,
+};
+
+mod udp;
+pub use udp::UdpSocket;
+
+pub mod unix;
+pub use unix::{UnixDatagram, UnixListener, UnixStream};
 
 // Re-exports
 pub use std::net::{
@@ -0,0 +1,361 @@
+//! Safe wrappers around the socket syscalls that the standard library does
+//! not expose. It is built with the requirements of this runtime in mind.
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::os::unix::prelude::{FromRawFd, OsStrExt, OwnedFd, RawFd};
+use std::ptr;
+use std::time::Duration;
+
+/// Creates a new non-blocking socket with the close-on-exec flag set
+pub(crate) fn new(domain: libc::c_int, ty: libc::c_int) -> io::Result<RawFd> {
+    let ty = ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+    cvt(unsafe { libc::socket(domain, ty, 0) })
+}
+
+/// Returns the socket domain matching the address family
+pub(crate) fn domain(addr: &SocketAddr) -> libc::c_int {
+    match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    }
+}
+
+/// Initiates a connection on the socket. Since the socket is non-blocking,
+/// this will usually fail with `EINPROGRESS`
+pub(crate) fn connect(fd: RawFd, addr: &SocketAddr) -> io::Result<()> {
+    let (addr, len) = into_raw_addr(addr);
+    let addr = &addr as *const _ as *const libc::sockaddr;
+    cvt(unsafe { libc::connect(fd, addr, len) })?;
+    Ok(())
+}
+
+/// Initiates a connection on a Unix socket. Unlike TCP, this completes
+/// immediately unless the listener's backlog is full
+pub(crate) fn connect_unix(fd: RawFd, addr: &UnixSocketAddr) -> io::Result<()> {
+    let (addr, len) = into_raw_unix_addr(addr)?;
+    let addr = &addr as *const _ as *const libc::sockaddr;
+    cvt(unsafe { libc::connect(fd, addr, len) })?;
+    Ok(())
+}
+
+/// Binds the socket to the given address
+pub(crate) fn bind(fd: RawFd, addr: &SocketAddr) -> io::Result<()> {
+    let (addr, len) = into_raw_addr(addr);
+    let addr = &addr as *const _ as *const libc::sockaddr;
+    cvt(unsafe { libc::bind(fd, addr, len) })?;
+    Ok(())
+}
+
+/// Marks the socket as accepting connections
+pub(crate) fn listen(fd: RawFd, backlog: u32) -> io::Result<()> {
+    cvt(unsafe { libc::listen(fd, c_int(backlog)) })?;
+    Ok(())
+}
+
+/// Returns the address the socket is bound to
+pub(crate) fn local_addr(fd: RawFd) -> io::Result<SocketAddr> {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let addr = &mut storage as *mut _ as *mut libc::sockaddr;
+    cvt(unsafe { libc::getsockname(fd, addr, &mut len) })?;
+    from_raw_addr(&storage)
+}
+
+/// Sets a socket option. `T` must match the type the kernel expects for
+/// the option, usually a `c_int`
+pub(crate) fn setsockopt<T>(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: T,
+) -> io::Result<()> {
+    let len = mem::size_of::<T>() as libc::socklen_t;
+    let value = &value as *const T as *const libc::c_void;
+    cvt(unsafe { libc::setsockopt(fd, level, name, value, len) })?;
+    Ok(())
+}
+
+/// Reads a socket option. `T` must match the type the kernel returns for
+/// the option, usually a `c_int`
+pub(crate) fn getsockopt<T>(fd: RawFd, level: libc::c_int, name: libc::c_int) -> io::Result<T> {
+    let mut value = mem::MaybeUninit::<T>::zeroed();
+    let mut len = mem::size_of::<T>() as libc::socklen_t;
+    let ptr = value.as_mut_ptr() as *mut libc::c_void;
+    cvt(unsafe { libc::getsockopt(fd, level, name, ptr, &mut len) })?;
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Converts a duration to whole seconds for a socket option. Rounds up so a
+/// sub-second duration doesn't become zero, and saturates instead of
+/// wrapping
+pub(crate) fn secs(duration: Duration) -> libc::c_int {
+    let secs = duration.as_secs() + (duration.subsec_nanos() > 0) as u64;
+    secs.min(libc::c_int::MAX as u64) as libc::c_int
+}
+
+/// Same as [`secs`] but in milliseconds
+pub(crate) fn millis(duration: Duration) -> libc::c_int {
+    let millis = duration.as_nanos().div_ceil(1_000_000);
+    millis.min(libc::c_int::MAX as u128) as libc::c_int
+}
+
+/// Clamps a size or count to the `c_int` the kernel expects
+pub(crate) fn c_int(value: u32) -> libc::c_int {
+    value.min(libc::c_int::MAX as u32) as libc::c_int
+}
+
+/// Sets `SO_LINGER`. With a duration, closing the socket blocks until
+/// pending data is sent or the duration elapses. The duration is rounded
+/// up to whole seconds
+pub(crate) fn set_linger(fd: RawFd, linger: Option<Duration>) -> io::Result<()> {
+    let linger = libc::linger {
+        l_onoff: linger.is_some() as libc::c_int,
+        l_linger: linger.map_or(0, secs),
+    };
+    setsockopt(fd, libc::SOL_SOCKET, libc::SO_LINGER, linger)
+}
+
+pub(crate) fn linger(fd: RawFd) -> io::Result<Option<Duration>> {
+    let linger: libc::linger = getsockopt(fd, libc::SOL_SOCKET, libc::SO_LINGER)?;
+    Ok((linger.l_onoff != 0).then(|| Duration::from_secs(linger.l_linger as u64)))
+}
+
+/// Reads the credentials of the process on the other end of a Unix socket
+/// through `SO_PEERCRED`
+pub(crate) fn peer_cred(fd: RawFd) -> io::Result<libc::ucred> {
+    getsockopt(fd, libc::SOL_SOCKET, libc::SO_PEERCRED)
+}
+
+/// Most file descriptors the kernel passes in one message
+/// (`SCM_MAX_FD` in the kernel, which `libc` doesn't export)
+const SCM_MAX_FD: usize = 253;
+
+/// Sends data over a Unix socket along with a set of file descriptors. The
+/// descriptors are passed as `SCM_RIGHTS` ancillary data
+pub(crate) fn send_with_fds(fd: RawFd, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+    check_fd_count(fds.len())?;
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let fds_len = mem::size_of_val(fds) as u32;
+    let mut control = control_buffer(fds.len());
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if !fds.is_empty() {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(fds_len) } as _;
+
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+            let data = libc::CMSG_DATA(cmsg) as *mut RawFd;
+            ptr::copy_nonoverlapping(fds.as_ptr(), data, fds.len());
+        }
+    }
+
+    // MSG_NOSIGNAL stops a closed peer from killing us with SIGPIPE
+    cvt_size(unsafe { libc::sendmsg(fd, &msg, libc::MSG_NOSIGNAL) })
+}
+
+/// Receives data from a Unix socket along with up to `max_fds` file
+/// descriptors. Returns the number of bytes read, the descriptors and
+/// whether any descriptors were closed by the kernel because they didn't
+/// fit. Received descriptors have the close-on-exec flag set
+pub(crate) fn recv_with_fds(
+    fd: RawFd,
+    buf: &mut [u8],
+    max_fds: usize,
+) -> io::Result<(usize, Vec<OwnedFd>, bool)> {
+    check_fd_count(max_fds)?;
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut control = control_buffer(max_fds);
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if max_fds > 0 {
+        let fds_len = (max_fds * mem::size_of::<RawFd>()) as u32;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(fds_len) } as _;
+    }
+
+    let n = cvt_size(unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_CMSG_CLOEXEC) })?;
+
+    let mut fds = Vec::new();
+    if max_fds > 0 {
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                    let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    for i in 0..len / mem::size_of::<RawFd>() {
+                        fds.push(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+    }
+
+    // Padding in the control buffer can leave room for more descriptors
+    // than asked for. Those are closed too, so `max_fds` holds either way
+    let mut truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+    if fds.len() > max_fds {
+        fds.truncate(max_fds);
+        truncated = true;
+    }
+
+    // The data has been read either way, so it is returned rather than
+    // lost along with the descriptors that didn't fit
+    Ok((n, fds, truncated))
+}
+
+/// Fails for more descriptors than fit in a message, which also keeps the
+/// control buffer's size from overflowing
+fn check_fd_count(n: usize) -> io::Result<()> {
+    if n > SCM_MAX_FD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("at most {} file descriptors fit in a message", SCM_MAX_FD),
+        ));
+    }
+    Ok(())
+}
+
+/// Allocates a buffer large enough to hold `n` file descriptors as
+/// ancillary data. It is backed by u64s to satisfy `cmsghdr` alignment
+fn control_buffer(n: usize) -> Vec<u64> {
+    let fds_len = (n * mem::size_of::<RawFd>()) as u32;
+    let space = unsafe { libc::CMSG_SPACE(fds_len) } as usize;
+    vec![0; space.div_ceil(mem::size_of::<u64>())]
+}
+
+/// Converts a [`SocketAddr`] into its C representation
+fn into_raw_addr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // Zeroed storage is a valid (unspecified) address for every family
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            unsafe { (&mut storage as *mut _ as *mut libc::sockaddr_in).write(raw) };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            unsafe { (&mut storage as *mut _ as *mut libc::sockaddr_in6).write(raw) };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+/// Converts the C representation of an IP address back into a
+/// [`SocketAddr`]
+fn from_raw_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let raw = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(raw.sin_addr.s_addr.to_ne_bytes());
+            let port = u16::from_be(raw.sin_port);
+            Ok(SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        }
+        libc::AF_INET6 => {
+            let raw = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(raw.sin6_addr.s6_addr);
+            let port = u16::from_be(raw.sin6_port);
+            let addr = SocketAddrV6::new(ip, port, raw.sin6_flowinfo, raw.sin6_scope_id);
+            Ok(SocketAddr::V6(addr))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid address family",
+        )),
+    }
+}
+
+/// Converts a Unix [`SocketAddr`](UnixSocketAddr) into its C representation.
+/// Abstract addresses are encoded with a leading null byte
+fn into_raw_unix_addr(addr: &UnixSocketAddr) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut raw: libc::sockaddr_un = unsafe { mem::zeroed() };
+    raw.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    // Pathnames need a trailing null byte, abstract names a leading one
+    let (bytes, offset, trailing) = if let Some(path) = addr.as_pathname() {
+        (path.as_os_str().as_bytes(), 0, 1)
+    } else if let Some(name) = addr.as_abstract_name() {
+        (name, 1, 0)
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot connect to an unnamed address",
+        ));
+    };
+
+    if offset + bytes.len() + trailing > raw.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "address must be shorter than SUN_LEN",
+        ));
+    }
+
+    for (dst, src) in raw.sun_path[offset..].iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+
+    let len = mem::size_of::<libc::sa_family_t>() + offset + bytes.len() + trailing;
+    Ok((raw, len as libc::socklen_t))
+}
+
+// Converts C error codes into a Rust Result type
+fn cvt(result: i32) -> io::Result<i32> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+// Same as `cvt` but for calls returning `ssize_t`
+fn cvt_size(result: isize) -> io::Result<usize> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result as usize)
+    }
+}
@@ -0,0 +1,212 @@
+//! Connection racing as described by Happy Eyeballs
+//! ([RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305))
+//!
+//! Addresses are interleaved by family and connection attempts are started
+//! one after the other, each given a head start before the next begins.
+//! The first attempt to succeed wins and the rest are dropped.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+use futures::stream::{FuturesUnordered, StreamExt};
+
+use super::TcpStream;
+use crate::time::try_sleep;
+
+/// The delay between connection attempts recommended by the RFC
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Error returned when every connection attempt failed. Holds the error
+/// encountered for each address
+#[derive(Debug)]
+pub struct ConnectError {
+    errors: Vec<(SocketAddr, io::Error)>,
+}
+
+pub(super) async fn connect<I>(addrs: I, attempt_delay: Duration) -> io::Result<TcpStream>
+where
+    I: Iterator<Item = SocketAddr>,
+{
+    let mut addrs = interleave(addrs).into_iter().peekable();
+    let mut attempts = FuturesUnordered::new();
+    let mut errors = Vec::new();
+
+    loop {
+        if let Some(addr) = addrs.next() {
+            tracing::debug!("Happy eyeballs: connecting to {}", addr);
+            attempts.push(async move { (addr, TcpStream::connect_addr(addr).await) });
+        }
+
+        if attempts.is_empty() {
+            break;
+        }
+
+        // While there are addresses left, the running attempts get a head
+        // start before we start the next one. Once we've run out, or if the
+        // runtime has timers disabled, all we can do is wait
+        let delay = match addrs.peek() {
+            Some(_) => try_sleep(attempt_delay).ok(),
+            None => None,
+        };
+        let finished = match delay {
+            Some(delay) => match select(attempts.next(), delay).await {
+                Either::Left((finished, _)) => finished,
+                Either::Right(_) => continue,
+            },
+            None => attempts.next().await,
+        };
+
+        // A failed attempt starts the next one straight away
+        match finished {
+            Some((_, Ok(stream))) => return Ok(stream),
+            Some((addr, Err(e))) => errors.push((addr, e)),
+            None => unreachable!("polled empty set of connection attempts"),
+        }
+    }
+
+    if errors.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any of the addresses",
+        ));
+    }
+
+    // Surface the kind of the last failure so callers matching on it
+    // behave the same as with a sequential connect
+    let kind = errors.last().map(|(_, e)| e.kind()).unwrap();
+    Err(io::Error::new(kind, ConnectError { errors }))
+}
+
+/// Orders addresses so their families alternate, starting with the family
+/// of the first address
+fn interleave<I: Iterator<Item = SocketAddr>>(addrs: I) -> Vec<SocketAddr> {
+    let mut addrs = addrs.peekable();
+    let prefer_v6 = matches!(addrs.peek(), Some(SocketAddr::V6(_)));
+
+    let (v6, v4): (VecDeque<_>, VecDeque<_>) = addrs.partition(|addr| addr.is_ipv6());
+    let (mut first, mut second) = if prefer_v6 { (v6, v4) } else { (v4, v6) };
+
+    let mut interleaved = Vec::with_capacity(first.len() + second.len());
+    while !first.is_empty() || !second.is_empty() {
+        interleaved.extend(first.pop_front());
+        interleaved.extend(second.pop_front());
+    }
+    interleaved
+}
+
+// ===== impl ConnectError =====
+
+impl ConnectError {
+    /// The address and error of every failed connection attempt, in the
+    /// order they failed
+    pub fn errors(&self) -> &[(SocketAddr, io::Error)] {
+        &self.errors
+    }
+}
+
+impl Error for ConnectError {}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not connect to any address: ")?;
+        for (i, (addr, e)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ({})", addr, e)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::TcpListener;
+    use crate::runtime::Builder;
+    use crate::Runtime;
+
+    fn v4(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn v6(port: u16) -> SocketAddr {
+        SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], port))
+    }
+
+    fn refused_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    fn interleave_families() {
+        let addrs = vec![v6(1), v6(2), v6(3), v4(4), v4(5)];
+        let interleaved = interleave(addrs.into_iter());
+        assert_eq!(interleaved, vec![v6(1), v4(4), v6(2), v4(5), v6(3)]);
+
+        let addrs = vec![v4(1), v6(2), v6(3)];
+        let interleaved = interleave(addrs.into_iter());
+        assert_eq!(interleaved, vec![v4(1), v6(2), v6(3)]);
+    }
+
+    #[test]
+    fn connect_first_success() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = TcpListener::bind(v4(0)).await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let addrs = [v4(refused_port()), addr];
+            TcpStream::connect_happy_eyeballs(&addrs[..], CONNECTION_ATTEMPT_DELAY)
+                .await
+                .unwrap();
+            listener.accept().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn connect_with_time_disabled() {
+        let rt = Builder::new_current_thread()
+            .enable_time(false)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind(v4(0)).await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            // Without timers the attempts are made one after the other
+            let addrs = [v4(refused_port()), addr];
+            TcpStream::connect_happy_eyeballs(&addrs[..], CONNECTION_ATTEMPT_DELAY)
+                .await
+                .unwrap();
+            listener.accept().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn connect_all_fail() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let addrs = [v4(refused_port()), v4(refused_port())];
+            let err = TcpStream::connect_happy_eyeballs(&addrs[..], CONNECTION_ATTEMPT_DELAY)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+            let err = err.get_ref().unwrap().downcast_ref::<ConnectError>();
+            let failed: Vec<_> = err.unwrap().errors().iter().map(|(a, _)| *a).collect();
+            assert_eq!(failed.len(), 2);
+            assert!(addrs.iter().all(|addr| failed.contains(addr)));
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+use futures::{ready, Stream};
+
+use super::TcpStream;
+use crate::io::io_source::Direction;
+use crate::io::pollable::Pollable;
+use crate::net::addr::ToSocketAddrs;
+
+/// A TCP socket server, listening for connections
+pub struct TcpListener {
+    inner: Pollable<std::net::TcpListener>,
+}
+
+/// Stream of connections accepted by a [`TcpListener`]. Created by
+/// [`TcpListener::incoming`]
+pub struct Incoming<'a> {
+    listener: &'a TcpListener,
+}
+
+// ===== impl TcpListener =====
+
+impl TcpListener {
+    /// Creates a listener bound to the first of the given addresses that
+    /// succeeds
+    pub async fn bind<A: ToSocketAddrs>(addrs: A) -> io::Result<TcpListener> {
+        let mut last_err = None;
+
+        for addr in addrs.to_socket_addrs().await? {
+            match std::net::TcpListener::bind(addr) {
+                Ok(listener) => return TcpListener::from_std(listener),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any of the addresses",
+            )
+        }))
+    }
+
+    /// Creates a woi [`TcpListener`] from a std listener. The listener is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(listener: std::net::TcpListener) -> io::Result<TcpListener> {
+        listener.set_nonblocking(true)?;
+        let pollable = Pollable::new(listener)?;
+        Ok(TcpListener { inner: pollable })
+    }
+
+    /// Accepts a new incoming connection, returning the stream and the
+    /// address of the peer
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Polls to accept a new incoming connection. If no connection is
+    /// waiting, the waker is registered with the reactor and woken once
+    /// the listener becomes readable
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let (stream, addr) = ready!(self
+            .inner
+            .poll_io(Direction::Read, cx, |listener| listener.accept()))?;
+        let stream = TcpStream::from_std(stream)?;
+        Poll::Ready(Ok((stream, addr)))
+    }
+
+    /// Returns a stream of incoming connections
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self }
+    }
+
+    /// Returns the local address this listener is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+}
+
+// ===== impl Incoming =====
+
+impl Stream for Incoming<'_> {
+    type Item = io::Result<TcpStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (stream, _) = ready!(self.listener.poll_accept(cx))?;
+        Poll::Ready(Some(Ok(stream)))
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::Runtime;
+
+    #[test]
+    fn accept_connection() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let handle = crate::spawn(async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream.write_all(b"hello").await.unwrap();
+            });
+
+            let (mut stream, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer.ip(), addr.ip());
+
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            handle.await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,14 @@
+mod happy_eyeballs;
+pub use happy_eyeballs::{ConnectError, CONNECTION_ATTEMPT_DELAY};
+
+mod listener;
+pub use listener::{Incoming, TcpListener};
+
+mod socket;
+pub use socket::TcpSocket;
+
+mod split;
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, ReuniteError, WriteHalf};
+
+mod stream;
+pub use stream::TcpStream;
@@ -0,0 +1,248 @@
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+use futures::future::poll_fn;
+
+use super::{TcpListener, TcpStream};
+use crate::io::pollable::Pollable;
+use crate::net::socket;
+
+/// A TCP socket that has not yet been turned into a stream or listener
+///
+/// It is used to set socket options that must be in place before
+/// connecting or listening, such as `SO_REUSEADDR`. Call
+/// [`connect`](TcpSocket::connect) to get a [`TcpStream`] or
+/// [`listen`](TcpSocket::listen) to get a [`TcpListener`].
+pub struct TcpSocket {
+    fd: OwnedFd,
+}
+
+impl TcpSocket {
+    /// Creates a new IPv4 socket
+    pub fn new_v4() -> io::Result<TcpSocket> {
+        TcpSocket::new(libc::AF_INET)
+    }
+
+    /// Creates a new IPv6 socket
+    pub fn new_v6() -> io::Result<TcpSocket> {
+        TcpSocket::new(libc::AF_INET6)
+    }
+
+    /// Creates a new socket matching the family of the address
+    pub(crate) fn new_for_addr(addr: &SocketAddr) -> io::Result<TcpSocket> {
+        TcpSocket::new(socket::domain(addr))
+    }
+
+    fn new(domain: libc::c_int) -> io::Result<TcpSocket> {
+        let fd = socket::new(domain, libc::SOCK_STREAM)?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(TcpSocket { fd })
+    }
+
+    /// Binds the socket to the given address
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<()> {
+        socket::bind(self.as_raw_fd(), &addr)
+    }
+
+    /// Returns the local address this socket is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(self.as_raw_fd())
+    }
+
+    /// Connects to the given address without blocking the executor. The
+    /// connection is started on the non-blocking socket and we wait for
+    /// the socket to become writable, which signals the handshake has
+    /// finished
+    pub async fn connect(self, addr: SocketAddr) -> io::Result<TcpStream> {
+        match socket::connect(self.as_raw_fd(), &addr) {
+            Ok(()) => {}
+            Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+            Err(e) => return Err(e),
+        }
+
+        let inner = Pollable::new(std::net::TcpStream::from(self.fd))?;
+        poll_fn(|cx| inner.poll_writable(cx)).await?;
+
+        // The socket is writable whether the connection succeeded or
+        // failed. SO_ERROR tells us which
+        if let Some(e) = inner.get_ref().take_error()? {
+            return Err(e);
+        }
+
+        Ok(TcpStream::from_pollable(inner))
+    }
+
+    /// Starts listening for connections, with `backlog` setting the
+    /// maximum number of pending connections
+    pub fn listen(self, backlog: u32) -> io::Result<TcpListener> {
+        socket::listen(self.as_raw_fd(), backlog)?;
+        TcpListener::from_std(std::net::TcpListener::from(self.fd))
+    }
+
+    // ===== Socket options =====
+
+    /// Sets `SO_REUSEADDR`, allowing the socket to bind to an address in
+    /// the `TIME_WAIT` state
+    pub fn set_reuseaddr(&self, reuseaddr: bool) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEADDR,
+            reuseaddr as libc::c_int,
+        )
+    }
+
+    pub fn reuseaddr(&self) -> io::Result<bool> {
+        let fd = self.as_raw_fd();
+        let reuseaddr: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR)?;
+        Ok(reuseaddr != 0)
+    }
+
+    /// Sets `SO_REUSEPORT`, allowing several sockets to bind to the same
+    /// address. The kernel balances connections between them
+    pub fn set_reuseport(&self, reuseport: bool) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            reuseport as libc::c_int,
+        )
+    }
+
+    pub fn reuseport(&self) -> io::Result<bool> {
+        let fd = self.as_raw_fd();
+        let reuseport: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT)?;
+        Ok(reuseport != 0)
+    }
+
+    /// Sets the size of the send buffer. The kernel doubles the value to
+    /// leave room for bookkeeping
+    pub fn set_send_buffer_size(&self, size: u32) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(fd, libc::SOL_SOCKET, libc::SO_SNDBUF, socket::c_int(size))
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<u32> {
+        let fd = self.as_raw_fd();
+        let size: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_SNDBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Sets the size of the receive buffer. The kernel doubles the value to
+    /// leave room for bookkeeping
+    pub fn set_recv_buffer_size(&self, size: u32) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVBUF, socket::c_int(size))
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<u32> {
+        let fd = self.as_raw_fd();
+        let size: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Sets `SO_KEEPALIVE`. See [`TcpStream::set_keepalive`] for tuning the
+    /// probes once connected
+    pub fn set_keepalive(&self, keepalive: bool) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_KEEPALIVE,
+            keepalive as libc::c_int,
+        )
+    }
+
+    pub fn keepalive(&self) -> io::Result<bool> {
+        let fd = self.as_raw_fd();
+        let keepalive: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE)?;
+        Ok(keepalive != 0)
+    }
+
+    /// Sets `SO_LINGER`. See [`TcpStream::set_linger`]
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        socket::set_linger(self.as_raw_fd(), linger)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        socket::linger(self.as_raw_fd())
+    }
+
+    /// Sets `TCP_NODELAY`, disabling Nagle's algorithm
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_NODELAY,
+            nodelay as libc::c_int,
+        )
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        let fd = self.as_raw_fd();
+        let nodelay: libc::c_int = socket::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY)?;
+        Ok(nodelay != 0)
+    }
+}
+
+impl AsRawFd for TcpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn listen_and_connect() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_reuseaddr(true).unwrap();
+            socket.set_reuseport(true).unwrap();
+            assert!(socket.reuseaddr().unwrap());
+            assert!(socket.reuseport().unwrap());
+
+            socket.bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+            let addr = socket.local_addr().unwrap();
+            let listener = socket.listen(128).unwrap();
+            assert_eq!(listener.local_addr().unwrap(), addr);
+
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_nodelay(true).unwrap();
+            socket.set_linger(Some(Duration::from_secs(1))).unwrap();
+            let stream = socket.connect(addr).await.unwrap();
+
+            assert!(stream.nodelay().unwrap());
+            assert_eq!(stream.linger().unwrap(), Some(Duration::from_secs(1)));
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+
+            stream.set_keepalive(true).unwrap();
+            stream.set_keepalive_idle(Duration::from_secs(30)).unwrap();
+            stream
+                .set_keepalive_interval(Duration::from_secs(5))
+                .unwrap();
+            stream.set_keepalive_retries(3).unwrap();
+            assert!(stream.keepalive().unwrap());
+            assert_eq!(stream.keepalive_idle().unwrap(), Duration::from_secs(30));
+            assert_eq!(stream.keepalive_interval().unwrap(), Duration::from_secs(5));
+            assert_eq!(stream.keepalive_retries().unwrap(), 3);
+
+            stream
+                .set_user_timeout(Some(Duration::from_millis(1500)))
+                .unwrap();
+            assert_eq!(
+                stream.user_timeout().unwrap(),
+                Some(Duration::from_millis(1500))
+            );
+        });
+    }
+}
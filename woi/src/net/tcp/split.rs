@@ -0,0 +1,271 @@
+//! Splitting a [`TcpStream`] into read and write halves
+//!
+//! Each half registers its waker in its own direction on the underlying IO
+//! source, so one task can be parked on a read while another is parked on
+//! a write to the same connection.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::TcpStream;
+use crate::io::{AsyncRead, AsyncWrite};
+
+/// Borrowed read half of a [`TcpStream`]. Created by [`TcpStream::split`]
+pub struct ReadHalf<'a> {
+    stream: &'a TcpStream,
+}
+
+/// Borrowed write half of a [`TcpStream`]. Created by [`TcpStream::split`]
+pub struct WriteHalf<'a> {
+    stream: &'a TcpStream,
+}
+
+/// Owned read half of a [`TcpStream`]. Created by [`TcpStream::into_split`]
+pub struct OwnedReadHalf {
+    stream: Arc<TcpStream>,
+}
+
+/// Owned write half of a [`TcpStream`]. Created by [`TcpStream::into_split`]
+///
+/// Dropping it shuts down the write direction of the stream, unless the
+/// halves were reunited
+pub struct OwnedWriteHalf {
+    stream: Arc<TcpStream>,
+    shutdown_on_drop: bool,
+}
+
+/// Error returned when reuniting halves that came from different streams.
+/// Gives the halves back
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+pub(super) fn split(stream: &mut TcpStream) -> (ReadHalf<'_>, WriteHalf<'_>) {
+    (ReadHalf { stream }, WriteHalf { stream })
+}
+
+pub(super) fn into_split(stream: TcpStream) -> (OwnedReadHalf, OwnedWriteHalf) {
+    let stream = Arc::new(stream);
+    let read = OwnedReadHalf {
+        stream: stream.clone(),
+    };
+    let write = OwnedWriteHalf {
+        stream,
+        shutdown_on_drop: true,
+    };
+    (read, write)
+}
+
+// ===== impl ReadHalf =====
+
+impl ReadHalf<'_> {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl AsyncRead for ReadHalf<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.stream.poll_read_shared(cx, buf)
+    }
+}
+
+// ===== impl WriteHalf =====
+
+impl WriteHalf<'_> {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl AsyncWrite for WriteHalf<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.stream.poll_write_shared(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.stream.shutdown(Shutdown::Write))
+    }
+}
+
+// ===== impl OwnedReadHalf =====
+
+impl OwnedReadHalf {
+    /// Puts the stream back together. Fails if the halves did not come from
+    /// the same call to [`TcpStream::into_split`]
+    pub fn reunite(self, other: OwnedWriteHalf) -> Result<TcpStream, ReuniteError> {
+        reunite(self, other)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.stream.poll_read_shared(cx, buf)
+    }
+}
+
+// ===== impl OwnedWriteHalf =====
+
+impl OwnedWriteHalf {
+    /// Puts the stream back together. Fails if the halves did not come from
+    /// the same call to [`TcpStream::into_split`]
+    pub fn reunite(self, other: OwnedReadHalf) -> Result<TcpStream, ReuniteError> {
+        reunite(other, self)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.stream.poll_write_shared(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let res = self.stream.shutdown(Shutdown::Write);
+        if res.is_ok() {
+            self.shutdown_on_drop = false;
+        }
+        Poll::Ready(res)
+    }
+}
+
+impl Drop for OwnedWriteHalf {
+    fn drop(&mut self) {
+        if self.shutdown_on_drop {
+            let _ = self.stream.shutdown(Shutdown::Write);
+        }
+    }
+}
+
+fn reunite(read: OwnedReadHalf, mut write: OwnedWriteHalf) -> Result<TcpStream, ReuniteError> {
+    if !Arc::ptr_eq(&read.stream, &write.stream) {
+        return Err(ReuniteError(read, write));
+    }
+
+    write.shutdown_on_drop = false;
+    drop(write);
+
+    // The write half was the only other owner and it's gone now
+    match Arc::try_unwrap(read.stream) {
+        Ok(stream) => Ok(stream),
+        Err(_) => unreachable!("TcpStream halves have other owners"),
+    }
+}
+
+// ===== impl ReuniteError =====
+
+impl Error for ReuniteError {}
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tried to reunite halves that are not from the same stream"
+        )
+    }
+}
+
+impl fmt::Debug for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReuniteError(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::net::TcpListener;
+    use crate::Runtime;
+
+    #[test]
+    fn owned_halves_in_separate_tasks() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            // Echo everything back to the client
+            crate::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                // Called as an associated function since `AsyncReadExt::split`
+                // would otherwise take precedence
+                let (mut read, mut write) = TcpStream::split(&mut stream);
+                let mut buf = [0; 5];
+                read.read_exact(&mut buf).await.unwrap();
+                write.write_all(&buf).await.unwrap();
+            });
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (mut read, mut write) = stream.into_split();
+
+            // The reader parks first, then the writer sends the data
+            let reader = crate::spawn(async move {
+                let mut buf = [0; 5];
+                read.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"hello");
+                read
+            });
+            let writer = crate::spawn(async move {
+                write.write_all(b"hello").await.unwrap();
+                write
+            });
+
+            let read = reader.await.unwrap();
+            let write = writer.await.unwrap();
+            read.reunite(write).unwrap();
+        });
+    }
+}
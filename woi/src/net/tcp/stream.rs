@@ -0,0 +1,339 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::split::{self, OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use super::{happy_eyeballs, TcpSocket};
+use crate::io::io_source::Direction;
+use crate::io::{pollable::Pollable, AsyncRead, AsyncWrite};
+use crate::net::addr::ToSocketAddrs;
+use crate::net::socket;
+
+pub struct TcpStream {
+    inner: Pollable<std::net::TcpStream>,
+}
+
+impl TcpStream {
+    pub async fn connect<A: ToSocketAddrs>(addrs: A) -> io::Result<TcpStream> {
+        let mut last_err = None;
+
+        for addr in addrs.to_socket_addrs().await? {
+            match TcpStream::connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any of the addresses",
+            )
+        }))
+    }
+
+    /// Connects using Happy Eyeballs ([RFC 8305]). Addresses are interleaved
+    /// by family and connection attempts are raced, with each attempt getting
+    /// `attempt_delay` of a head start before the next one begins. The RFC
+    /// recommends [`CONNECTION_ATTEMPT_DELAY`]. If every attempt fails, the
+    /// returned error wraps a [`ConnectError`] listing each failure
+    ///
+    /// On a runtime with timers disabled, there are no head starts and the
+    /// addresses are tried one after the other instead
+    ///
+    /// [RFC 8305]: https://datatracker.ietf.org/doc/html/rfc8305
+    /// [`CONNECTION_ATTEMPT_DELAY`]: crate::net::CONNECTION_ATTEMPT_DELAY
+    /// [`ConnectError`]: crate::net::ConnectError
+    pub async fn connect_happy_eyeballs<A: ToSocketAddrs>(
+        addrs: A,
+        attempt_delay: Duration,
+    ) -> io::Result<TcpStream> {
+        let addrs = addrs.to_socket_addrs().await?;
+        happy_eyeballs::connect(addrs, attempt_delay).await
+    }
+
+    /// Connects to a single address without blocking the executor
+    pub(super) async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = TcpSocket::new_for_addr(&addr)?;
+        socket.connect(addr).await
+    }
+
+    /// Creates a woi [`TcpStream`] from a std stream. The stream is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(stream: std::net::TcpStream) -> io::Result<TcpStream> {
+        stream.set_nonblocking(true)?;
+        let pollable = Pollable::new(stream)?;
+        Ok(TcpStream { inner: pollable })
+    }
+
+    /// Wraps a stream that's already registered with the reactor
+    pub(super) fn from_pollable(inner: Pollable<std::net::TcpStream>) -> TcpStream {
+        TcpStream { inner }
+    }
+
+    /// Splits the stream into a read half and a write half borrowing it.
+    /// The halves can be used concurrently, for example in a `join!`
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        split::split(self)
+    }
+
+    /// Splits the stream into owned read and write halves, which can be
+    /// moved into separate tasks. Use [`OwnedReadHalf::reunite`] to put the
+    /// stream back together. Dropping the write half shuts down writing
+    ///
+    /// [`OwnedReadHalf::reunite`]: crate::net::tcp::OwnedReadHalf::reunite
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        split::into_split(self)
+    }
+
+    /// Same as [`AsyncRead::poll_read`] but through a shared reference. The
+    /// reader and writer wakers are kept separately so one task can read
+    /// while another writes
+    pub(super) fn poll_read_shared(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |mut stream| stream.read(buf))
+    }
+
+    /// Same as [`AsyncWrite::poll_write`] but through a shared reference
+    pub(super) fn poll_write_shared(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Write, cx, |mut stream| stream.write(buf))
+    }
+
+    pub(super) fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.get_ref().shutdown(how)
+    }
+
+    /// Returns the address of the local half of this connection
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+
+    /// Returns the address of the remote half of this connection
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().peer_addr()
+    }
+
+    // ===== Socket options =====
+
+    /// Sets `TCP_NODELAY`, disabling Nagle's algorithm. Small writes are
+    /// sent straight away instead of being batched
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.get_ref().set_nodelay(nodelay)
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.inner.get_ref().nodelay()
+    }
+
+    /// Sets the time-to-live of outgoing packets
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.inner.get_ref().set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.inner.get_ref().ttl()
+    }
+
+    /// Sets `SO_KEEPALIVE`. When enabled, probes are sent on an idle
+    /// connection to detect a peer that has gone away
+    pub fn set_keepalive(&self, keepalive: bool) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_KEEPALIVE,
+            keepalive as libc::c_int,
+        )
+    }
+
+    pub fn keepalive(&self) -> io::Result<bool> {
+        let fd = self.as_raw_fd();
+        let keepalive: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE)?;
+        Ok(keepalive != 0)
+    }
+
+    /// Sets `TCP_KEEPIDLE`, how long the connection must be idle before
+    /// keepalive probes are sent. Has a granularity of seconds, so it is
+    /// rounded up to at least one
+    pub fn set_keepalive_idle(&self, idle: Duration) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        let idle = socket::secs(idle).max(1);
+        socket::setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, idle)
+    }
+
+    pub fn keepalive_idle(&self) -> io::Result<Duration> {
+        let fd = self.as_raw_fd();
+        let idle: libc::c_int = socket::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE)?;
+        Ok(Duration::from_secs(idle as u64))
+    }
+
+    /// Sets `TCP_KEEPINTVL`, the time between keepalive probes. Has a
+    /// granularity of seconds, so it is rounded up to at least one
+    pub fn set_keepalive_interval(&self, interval: Duration) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        let interval = socket::secs(interval).max(1);
+        socket::setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, interval)
+    }
+
+    pub fn keepalive_interval(&self) -> io::Result<Duration> {
+        let fd = self.as_raw_fd();
+        let interval: libc::c_int = socket::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL)?;
+        Ok(Duration::from_secs(interval as u64))
+    }
+
+    /// Sets `TCP_KEEPCNT`, the number of unanswered keepalive probes
+    /// before the connection is dropped
+    pub fn set_keepalive_retries(&self, retries: u32) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        let retries = socket::c_int(retries);
+        socket::setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, retries)
+    }
+
+    pub fn keepalive_retries(&self) -> io::Result<u32> {
+        let fd = self.as_raw_fd();
+        let retries: libc::c_int = socket::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT)?;
+        Ok(retries as u32)
+    }
+
+    /// Sets `SO_LINGER`. With a duration, closing the stream blocks until
+    /// pending data is sent or the duration elapses. A zero duration resets
+    /// the connection on close
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        socket::set_linger(self.as_raw_fd(), linger)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        socket::linger(self.as_raw_fd())
+    }
+
+    /// Sets the size of the send buffer. The kernel doubles the value to
+    /// leave room for bookkeeping
+    pub fn set_send_buffer_size(&self, size: u32) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(fd, libc::SOL_SOCKET, libc::SO_SNDBUF, socket::c_int(size))
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<u32> {
+        let fd = self.as_raw_fd();
+        let size: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_SNDBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Sets the size of the receive buffer. The kernel doubles the value to
+    /// leave room for bookkeeping
+    pub fn set_recv_buffer_size(&self, size: u32) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        socket::setsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVBUF, socket::c_int(size))
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<u32> {
+        let fd = self.as_raw_fd();
+        let size: libc::c_int = socket::getsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVBUF)?;
+        Ok(size as u32)
+    }
+
+    /// Sets `TCP_USER_TIMEOUT`, how long sent data may go unacknowledged
+    /// before the connection is dropped. `None` uses the system default.
+    /// Has a granularity of milliseconds and is capped at `c_int::MAX` of
+    /// them, about 24 days
+    pub fn set_user_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        let timeout = timeout.map_or(0, |t| socket::millis(t) as libc::c_uint);
+        socket::setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT, timeout)
+    }
+
+    pub fn user_timeout(&self) -> io::Result<Option<Duration>> {
+        let fd = self.as_raw_fd();
+        let timeout: libc::c_uint =
+            socket::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT)?;
+        Ok((timeout != 0).then(|| Duration::from_millis(timeout as u64)))
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.inner.get_ref().shutdown(Shutdown::Write))
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn connect_refused() {
+        // Grab a free port and close it again so nothing is listening on it
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let err = TcpStream::connect(addr).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        });
+    }
+
+    #[test]
+    fn clamp_durations() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+
+            stream.set_keepalive_idle(Duration::from_millis(500)).unwrap();
+            assert_eq!(stream.keepalive_idle().unwrap(), Duration::from_secs(1));
+            stream.set_keepalive_interval(Duration::ZERO).unwrap();
+            assert_eq!(stream.keepalive_interval().unwrap(), Duration::from_secs(1));
+
+            let days = Duration::from_secs(60 * 60 * 24 * 60);
+            stream.set_user_timeout(Some(days)).unwrap();
+            let max = Duration::from_millis(libc::c_int::MAX as u64);
+            assert_eq!(stream.user_timeout().unwrap(), Some(max));
+        });
+    }
+}
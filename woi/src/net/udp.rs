@@ -0,0 +1,273 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+
+use crate::io::io_source::Direction;
+use crate::io::pollable::Pollable;
+use crate::net::addr::ToSocketAddrs;
+
+/// A UDP socket
+///
+/// After binding, datagrams can be sent to and received from any address.
+/// Once connected, [`send`] and [`recv`] can be used to talk to that single
+/// peer.
+///
+/// [`send`]: UdpSocket::send
+/// [`recv`]: UdpSocket::recv
+pub struct UdpSocket {
+    inner: Pollable<std::net::UdpSocket>,
+}
+
+impl UdpSocket {
+    /// Creates a socket bound to the first of the given addresses that
+    /// succeeds
+    pub async fn bind<A: ToSocketAddrs>(addrs: A) -> io::Result<UdpSocket> {
+        let mut last_err = None;
+
+        for addr in addrs.to_socket_addrs().await? {
+            match std::net::UdpSocket::bind(addr) {
+                Ok(socket) => return UdpSocket::from_std(socket),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any of the addresses",
+            )
+        }))
+    }
+
+    /// Creates a woi [`UdpSocket`] from a std socket. The socket is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(socket: std::net::UdpSocket) -> io::Result<UdpSocket> {
+        socket.set_nonblocking(true)?;
+        let inner = Pollable::new(socket)?;
+        Ok(UdpSocket { inner })
+    }
+
+    /// Sets the default address to send to and restricts the addresses we
+    /// receive from. Connecting a UDP socket never blocks
+    pub async fn connect<A: ToSocketAddrs>(&self, addrs: A) -> io::Result<()> {
+        let mut last_err = None;
+
+        for addr in addrs.to_socket_addrs().await? {
+            match self.inner.get_ref().connect(addr) {
+                Ok(()) => return Ok(()),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any of the addresses",
+            )
+        }))
+    }
+
+    /// Sends data to the connected peer, returning the number of bytes
+    /// written
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Write, cx, |socket| socket.send(buf))
+    }
+
+    /// Receives a datagram from the connected peer, returning the number of
+    /// bytes read
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |socket| socket.recv(buf))
+    }
+
+    /// Sends data to the given address, returning the number of bytes
+    /// written. Only the first resolved address is used
+    pub async fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], target: A) -> io::Result<usize> {
+        match target.to_socket_addrs().await?.next() {
+            Some(target) => poll_fn(|cx| self.poll_send_to(cx, buf, target)).await,
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no addresses to send data to",
+            )),
+        }
+    }
+
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Write, cx, |socket| socket.send_to(buf, target))
+    }
+
+    /// Receives a datagram, returning the number of bytes read and the
+    /// address it came from
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |socket| socket.recv_from(buf))
+    }
+
+    /// Receives a datagram without removing it from the queue, returning
+    /// the number of bytes read and the address it came from
+    pub async fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_peek_from(cx, buf)).await
+    }
+
+    pub fn poll_peek_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |socket| socket.peek_from(buf))
+    }
+
+    /// Returns the local address this socket is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+
+    /// Returns the address of the peer this socket is connected to
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().peer_addr()
+    }
+
+    // ===== Socket options =====
+
+    /// Sets the `SO_BROADCAST` option, allowing datagrams to be sent to
+    /// a broadcast address
+    pub fn set_broadcast(&self, on: bool) -> io::Result<()> {
+        self.inner.get_ref().set_broadcast(on)
+    }
+
+    pub fn broadcast(&self) -> io::Result<bool> {
+        self.inner.get_ref().broadcast()
+    }
+
+    /// Joins an IPv4 multicast group on the given interface. Use
+    /// [`Ipv4Addr::UNSPECIFIED`] to let the system choose the interface
+    pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        self.inner
+            .get_ref()
+            .join_multicast_v4(&multiaddr, &interface)
+    }
+
+    pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        self.inner
+            .get_ref()
+            .leave_multicast_v4(&multiaddr, &interface)
+    }
+
+    /// Joins an IPv6 multicast group on the interface with the given index.
+    /// An index of 0 lets the system choose the interface
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.inner.get_ref().join_multicast_v6(multiaddr, interface)
+    }
+
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.inner
+            .get_ref()
+            .leave_multicast_v6(multiaddr, interface)
+    }
+
+    /// Sets whether multicast packets we send are looped back to our
+    /// own sockets
+    pub fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()> {
+        self.inner.get_ref().set_multicast_loop_v4(on)
+    }
+
+    pub fn multicast_loop_v4(&self) -> io::Result<bool> {
+        self.inner.get_ref().multicast_loop_v4()
+    }
+
+    pub fn set_multicast_loop_v6(&self, on: bool) -> io::Result<()> {
+        self.inner.get_ref().set_multicast_loop_v6(on)
+    }
+
+    pub fn multicast_loop_v6(&self) -> io::Result<bool> {
+        self.inner.get_ref().multicast_loop_v6()
+    }
+
+    /// Sets the time-to-live of outgoing IPv4 multicast packets
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()> {
+        self.inner.get_ref().set_multicast_ttl_v4(ttl)
+    }
+
+    pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        self.inner.get_ref().multicast_ttl_v4()
+    }
+
+    /// Sets the time-to-live of outgoing packets
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.inner.get_ref().set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.inner.get_ref().ttl()
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn send_recv() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let any = SocketAddr::from(([127, 0, 0, 1], 0));
+            let a = UdpSocket::bind(any).await.unwrap();
+            let b = UdpSocket::bind(any).await.unwrap();
+            let a_addr = a.local_addr().unwrap();
+            let b_addr = b.local_addr().unwrap();
+
+            a.send_to(b"ping", b_addr).await.unwrap();
+
+            let mut buf = [0; 16];
+            let (n, from) = b.peek_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"ping");
+            assert_eq!(from, a_addr);
+
+            // Peeking leaves the datagram in the queue
+            let (n, from) = b.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"ping");
+            assert_eq!(from, a_addr);
+
+            b.connect(a_addr).await.unwrap();
+            assert_eq!(b.peer_addr().unwrap(), a_addr);
+            b.send(b"pong").await.unwrap();
+
+            let n = a.recv(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"pong");
+        });
+    }
+}
@@ -0,0 +1,210 @@
+use std::io;
+use std::os::unix::prelude::{AsRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+
+use super::{SocketAddr, UCred};
+use crate::io::io_source::Direction;
+use crate::io::pollable::Pollable;
+use crate::net::socket;
+
+/// A Unix datagram socket
+pub struct UnixDatagram {
+    inner: Pollable<std::os::unix::net::UnixDatagram>,
+}
+
+impl UnixDatagram {
+    /// Creates a socket bound to the given path
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
+        let socket = std::os::unix::net::UnixDatagram::bind(path)?;
+        UnixDatagram::from_std(socket)
+    }
+
+    /// Creates a socket bound to the given address. This is used to bind
+    /// to sockets in the abstract namespace
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixDatagram> {
+        let socket = std::os::unix::net::UnixDatagram::bind_addr(addr)?;
+        UnixDatagram::from_std(socket)
+    }
+
+    /// Creates a socket that isn't bound to any address
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        UnixDatagram::from_std(socket)
+    }
+
+    /// Creates a pair of connected sockets
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (a, b) = std::os::unix::net::UnixDatagram::pair()?;
+        Ok((UnixDatagram::from_std(a)?, UnixDatagram::from_std(b)?))
+    }
+
+    /// Creates a woi [`UnixDatagram`] from a std socket. The socket is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(socket: std::os::unix::net::UnixDatagram) -> io::Result<UnixDatagram> {
+        socket.set_nonblocking(true)?;
+        let inner = Pollable::new(socket)?;
+        Ok(UnixDatagram { inner })
+    }
+
+    /// Sets the default address to send to and restricts the addresses we
+    /// receive from
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.inner.get_ref().connect(path)
+    }
+
+    /// Like [`connect`](UnixDatagram::connect) but takes an address. This
+    /// is used to connect to sockets in the abstract namespace
+    pub fn connect_addr(&self, addr: &SocketAddr) -> io::Result<()> {
+        self.inner.get_ref().connect_addr(addr)
+    }
+
+    /// Sends data to the connected peer, returning the number of bytes
+    /// written
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Write, cx, |socket| socket.send(buf))
+    }
+
+    /// Receives a datagram from the connected peer, returning the number of
+    /// bytes read
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |socket| socket.recv(buf))
+    }
+
+    /// Sends data to the socket at the given path, returning the number of
+    /// bytes written
+    pub async fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
+        let path = path.as_ref();
+        poll_fn(|cx| {
+            self.inner
+                .poll_io(Direction::Write, cx, |socket| socket.send_to(buf, path))
+        })
+        .await
+    }
+
+    /// Sends data to the socket at the given address, returning the number
+    /// of bytes written
+    pub async fn send_to_addr(&self, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        poll_fn(|cx| {
+            self.inner.poll_io(Direction::Write, cx, |socket| {
+                socket.send_to_addr(buf, addr)
+            })
+        })
+        .await
+    }
+
+    /// Receives a datagram, returning the number of bytes read and the
+    /// address it came from
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.inner
+            .poll_io(Direction::Read, cx, |socket| socket.recv_from(buf))
+    }
+
+    /// Sends data along with a set of file descriptors, returning the number
+    /// of bytes written. The descriptors stay open on our side
+    pub async fn send_with_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_send_with_fds(cx, buf, fds)).await
+    }
+
+    pub fn poll_send_with_fds(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        fds: &[RawFd],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_io(Direction::Write, cx, |socket| {
+            socket::send_with_fds(socket.as_raw_fd(), buf, fds)
+        })
+    }
+
+    /// Receives data along with up to `max_fds` file descriptors, returning
+    /// the number of bytes read, the descriptors received and whether any
+    /// were left out. If the peer sent more descriptors than fit, the kernel
+    /// closes the extra ones and the flag is set, but the data is still
+    /// returned. The descriptors can be wrapped back into woi types through
+    /// their `from_std` constructors
+    ///
+    /// Fails with `InvalidInput` if `max_fds` is more than the 253
+    /// descriptors the kernel passes in one message
+    pub async fn recv_with_fds(
+        &self,
+        buf: &mut [u8],
+        max_fds: usize,
+    ) -> io::Result<(usize, Vec<OwnedFd>, bool)> {
+        poll_fn(|cx| self.poll_recv_with_fds(cx, buf, max_fds)).await
+    }
+
+    pub fn poll_recv_with_fds(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        max_fds: usize,
+    ) -> Poll<io::Result<(usize, Vec<OwnedFd>, bool)>> {
+        self.inner.poll_io(Direction::Read, cx, |socket| {
+            socket::recv_with_fds(socket.as_raw_fd(), buf, max_fds)
+        })
+    }
+
+    /// Returns the local address this socket is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+
+    /// Returns the address of the peer this socket is connected to
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().peer_addr()
+    }
+
+    /// Returns the credentials of the process on the other end of this
+    /// socket. Only available for sockets created through
+    /// [`pair`](UnixDatagram::pair)
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        let cred = socket::peer_cred(self.inner.get_ref().as_raw_fd())?;
+        Ok(cred.into())
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn pair_send_recv() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (a, b) = UnixDatagram::pair().unwrap();
+            a.send(b"ping").await.unwrap();
+
+            let mut buf = [0; 16];
+            let n = b.recv(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"ping");
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::io;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+use futures::{ready, Stream};
+
+use super::{SocketAddr, UnixStream};
+use crate::io::io_source::Direction;
+use crate::io::pollable::Pollable;
+
+/// A Unix socket server, listening for connections
+pub struct UnixListener {
+    inner: Pollable<std::os::unix::net::UnixListener>,
+}
+
+/// Stream of connections accepted by a [`UnixListener`]. Created by
+/// [`UnixListener::incoming`]
+pub struct Incoming<'a> {
+    listener: &'a UnixListener,
+}
+
+// ===== impl UnixListener =====
+
+impl UnixListener {
+    /// Creates a listener bound to the given path. Binding never blocks so
+    /// unlike [`TcpListener::bind`](crate::net::TcpListener::bind) this
+    /// isn't async
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        let listener = std::os::unix::net::UnixListener::bind(path)?;
+        UnixListener::from_std(listener)
+    }
+
+    /// Creates a listener bound to the given address. This is used to bind
+    /// to sockets in the abstract namespace
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixListener> {
+        let listener = std::os::unix::net::UnixListener::bind_addr(addr)?;
+        UnixListener::from_std(listener)
+    }
+
+    /// Creates a woi [`UnixListener`] from a std listener. The listener is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(listener: std::os::unix::net::UnixListener) -> io::Result<UnixListener> {
+        listener.set_nonblocking(true)?;
+        let inner = Pollable::new(listener)?;
+        Ok(UnixListener { inner })
+    }
+
+    /// Accepts a new incoming connection, returning the stream and the
+    /// address of the peer
+    pub async fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Polls to accept a new incoming connection. If no connection is
+    /// waiting, the waker is registered with the reactor and woken once
+    /// the listener becomes readable
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(UnixStream, SocketAddr)>> {
+        let (stream, addr) = ready!(self
+            .inner
+            .poll_io(Direction::Read, cx, |listener| listener.accept()))?;
+        let stream = UnixStream::from_std(stream)?;
+        Poll::Ready(Ok((stream, addr)))
+    }
+
+    /// Returns a stream of incoming connections
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self }
+    }
+
+    /// Returns the local address this listener is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+}
+
+// ===== impl Incoming =====
+
+impl Stream for Incoming<'_> {
+    type Item = io::Result<UnixStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (stream, _) = ready!(self.listener.poll_accept(cx))?;
+        Poll::Ready(Some(Ok(stream)))
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
@@ -0,0 +1,21 @@
+//! Unix domain sockets
+//!
+//! Besides filesystem paths, sockets can be bound to Linux's abstract
+//! namespace. Build such an address with [`SocketAddrExt::from_abstract_name`]
+//! and pass it to the `*_addr` constructors.
+
+mod datagram;
+pub use datagram::UnixDatagram;
+
+mod listener;
+pub use listener::{Incoming, UnixListener};
+
+mod stream;
+pub use stream::UnixStream;
+
+mod ucred;
+pub use ucred::UCred;
+
+// Re-exports
+pub use std::os::linux::net::SocketAddrExt;
+pub use std::os::unix::net::SocketAddr;
@@ -0,0 +1,303 @@
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+
+use super::{SocketAddr, UCred};
+use crate::io::io_source::Direction;
+use crate::io::{pollable::Pollable, AsyncRead, AsyncWrite};
+use crate::net::socket;
+
+/// A stream connected to a Unix socket
+pub struct UnixStream {
+    inner: Pollable<std::os::unix::net::UnixStream>,
+}
+
+impl UnixStream {
+    /// Connects to the socket at the given path. Fails with
+    /// [`ConnectionRefused`](io::ErrorKind::ConnectionRefused) if the
+    /// listener's backlog is full
+    pub async fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        let addr = SocketAddr::from_pathname(path)?;
+        UnixStream::connect_addr(&addr).await
+    }
+
+    /// Connects to the socket at the given address. This is used to connect
+    /// to sockets in the abstract namespace. If the listener's backlog is
+    /// full, an error of kind
+    /// [`ConnectionRefused`](io::ErrorKind::ConnectionRefused) is returned
+    /// instead of waiting for it to free up
+    pub async fn connect_addr(addr: &SocketAddr) -> io::Result<UnixStream> {
+        let fd = socket::new(libc::AF_UNIX, libc::SOCK_STREAM)?;
+        // Hand the fd over to std straight away so it is closed if we bail
+        let stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+        match socket::connect_unix(fd, addr) {
+            Ok(()) => {}
+            Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+            // Unix sockets connect straight away or not at all. There is
+            // nothing to wait on when the backlog is full, and a bare
+            // `WouldBlock` would read as a spurious wakeup
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    "the listener's backlog is full",
+                ))
+            }
+            Err(e) => return Err(e),
+        }
+
+        let inner = Pollable::new(stream)?;
+        poll_fn(|cx| inner.poll_writable(cx)).await?;
+
+        if let Some(e) = inner.get_ref().take_error()? {
+            return Err(e);
+        }
+
+        Ok(UnixStream { inner })
+    }
+
+    /// Creates a pair of connected streams
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = std::os::unix::net::UnixStream::pair()?;
+        Ok((UnixStream::from_std(a)?, UnixStream::from_std(b)?))
+    }
+
+    /// Creates a woi [`UnixStream`] from a std stream. The stream is switched to
+    /// non-blocking mode and registered with the reactor
+    pub fn from_std(stream: std::os::unix::net::UnixStream) -> io::Result<UnixStream> {
+        stream.set_nonblocking(true)?;
+        let inner = Pollable::new(stream)?;
+        Ok(UnixStream { inner })
+    }
+
+    /// Sends data along with a set of file descriptors, returning the number
+    /// of bytes written. The descriptors stay open on our side
+    pub async fn send_with_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_send_with_fds(cx, buf, fds)).await
+    }
+
+    pub fn poll_send_with_fds(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        fds: &[RawFd],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_io(Direction::Write, cx, |stream| {
+            socket::send_with_fds(stream.as_raw_fd(), buf, fds)
+        })
+    }
+
+    /// Receives data along with up to `max_fds` file descriptors, returning
+    /// the number of bytes read, the descriptors received and whether any
+    /// were left out. If the peer sent more descriptors than fit, the kernel
+    /// closes the extra ones and the flag is set, but the data is still
+    /// returned. The descriptors can be wrapped back into woi types through
+    /// their `from_std` constructors
+    ///
+    /// Fails with `InvalidInput` if `max_fds` is more than the 253
+    /// descriptors the kernel passes in one message
+    pub async fn recv_with_fds(
+        &self,
+        buf: &mut [u8],
+        max_fds: usize,
+    ) -> io::Result<(usize, Vec<OwnedFd>, bool)> {
+        poll_fn(|cx| self.poll_recv_with_fds(cx, buf, max_fds)).await
+    }
+
+    pub fn poll_recv_with_fds(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        max_fds: usize,
+    ) -> Poll<io::Result<(usize, Vec<OwnedFd>, bool)>> {
+        self.inner.poll_io(Direction::Read, cx, |stream| {
+            socket::recv_with_fds(stream.as_raw_fd(), buf, max_fds)
+        })
+    }
+
+    /// Returns the address of the local half of this connection
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().local_addr()
+    }
+
+    /// Returns the address of the remote half of this connection
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.get_ref().peer_addr()
+    }
+
+    /// Returns the credentials of the process on the other end of this
+    /// connection
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        let cred = socket::peer_cred(self.inner.get_ref().as_raw_fd())?;
+        Ok(cred.into())
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.inner.get_ref().shutdown(Shutdown::Write))
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::net::unix::{SocketAddrExt, UnixListener};
+    use crate::Runtime;
+
+    #[test]
+    fn pair_read_write() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (mut a, mut b) = UnixStream::pair().unwrap();
+            a.write_all(b"hello").await.unwrap();
+
+            let mut buf = [0; 5];
+            b.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            let cred = b.peer_cred().unwrap();
+            assert_eq!(cred.uid(), unsafe { libc::getuid() });
+            assert_eq!(cred.pid(), std::process::id() as libc::pid_t);
+        });
+    }
+
+    #[test]
+    fn pass_fds() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (a, b) = UnixStream::pair().unwrap();
+            let (inner_a, inner_b) = std::os::unix::net::UnixStream::pair().unwrap();
+
+            a.send_with_fds(b"fd", &[inner_a.as_raw_fd()])
+                .await
+                .unwrap();
+            drop(inner_a);
+
+            let mut buf = [0; 2];
+            let (n, mut fds, truncated) = b.recv_with_fds(&mut buf, 4).await.unwrap();
+            assert_eq!(&buf[..n], b"fd");
+            assert_eq!(fds.len(), 1);
+            assert!(!truncated);
+
+            // The received fd is the other end of `inner_b`
+            let std_stream = std::os::unix::net::UnixStream::from(fds.remove(0));
+            let mut stream = UnixStream::from_std(std_stream).unwrap();
+            stream.write_all(b"hello").await.unwrap();
+
+            let mut inner_b = UnixStream::from_std(inner_b).unwrap();
+            let mut buf = [0; 5];
+            inner_b.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        });
+    }
+
+    #[test]
+    fn pass_too_many_fds() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (mut a, mut b) = UnixStream::pair().unwrap();
+            let (inner_a, _inner_b) = std::os::unix::net::UnixStream::pair().unwrap();
+            let fd = inner_a.as_raw_fd();
+
+            a.send_with_fds(b"fd", &[fd, fd, fd, fd]).await.unwrap();
+            a.write_all(b"after").await.unwrap();
+
+            // The data comes through along with the one descriptor that fit
+            let mut buf = [0; 2];
+            let (n, fds, truncated) = b.recv_with_fds(&mut buf, 1).await.unwrap();
+            assert_eq!(&buf[..n], b"fd");
+            assert_eq!(fds.len(), 1);
+            assert!(truncated);
+
+            // and the stream carries on where it left off
+            let mut buf = [0; 5];
+            b.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"after");
+
+            let err = b.recv_with_fds(&mut buf, 254).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = a.send_with_fds(b"fd", &[fd; 254]).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        });
+    }
+
+    #[test]
+    fn connect_abstract() {
+        let name = format!("woi-test-{}", std::process::id());
+        let addr = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = UnixListener::bind_addr(&addr).unwrap();
+
+            let handle = crate::spawn(async move {
+                let mut stream = UnixStream::connect_addr(&addr).await.unwrap();
+                stream.write_all(b"hello").await.unwrap();
+            });
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            handle.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn connect_full_backlog() {
+        let path = std::env::temp_dir().join(format!("woi-backlog-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let listener = UnixListener::bind(&path).unwrap();
+            // Only room for one pending connection
+            assert_eq!(unsafe { libc::listen(listener.as_raw_fd(), 0) }, 0);
+
+            let _first = UnixStream::connect(&path).await.unwrap();
+            let err = UnixStream::connect(&path).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+            assert_eq!(err.to_string(), "the listener's backlog is full");
+
+            listener.accept().await.unwrap();
+            UnixStream::connect(&path).await.unwrap();
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+/// Credentials of the process on the other end of a Unix socket
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UCred {
+    pid: libc::pid_t,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+impl UCred {
+    /// Process ID of the peer
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// User ID of the peer
+    pub fn uid(&self) -> libc::uid_t {
+        self.uid
+    }
+
+    /// Group ID of the peer
+    pub fn gid(&self) -> libc::gid_t {
+        self.gid
+    }
+}
+
+impl From<libc::ucred> for UCred {
+    fn from(cred: libc::ucred) -> UCred {
+        UCred {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        }
+    }
+}
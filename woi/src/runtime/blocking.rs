@@ -0,0 +1,246 @@
+//! A pool of threads for running blocking work off the executor thread
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender};
+
+use super::builder::{Builder, Callback};
+use crate::task::raw::Schedule;
+use crate::task::{JoinHandle, Task};
+
+/// Handle to the blocking pool. Threads are spawned on demand and shut
+/// down after sitting idle for a while
+#[derive(Clone)]
+pub(crate) struct BlockingPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Sending half of the job queue
+    tx: Sender<Job>,
+    /// Receiving half of the job queue. Shared by all threads
+    rx: Receiver<Job>,
+    /// Thread accounting, used to decide whether to spawn a new thread
+    threads: Mutex<Threads>,
+    /// Upper bound on the number of threads. Once reached, jobs wait for a
+    /// thread to free up
+    max_threads: usize,
+    /// How long a thread waits for new work before shutting down
+    keep_alive: Duration,
+    thread_name: String,
+    on_thread_start: Option<Callback>,
+    on_thread_stop: Option<Callback>,
+}
+
+#[derive(Default)]
+struct Threads {
+    /// Number of threads alive
+    total: usize,
+    /// Number of threads blocked waiting for work
+    idle: usize,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Future running the closure of [`spawn_blocking`](crate::task::spawn_blocking)
+/// the first time it is polled
+struct BlockingTask<F> {
+    func: Option<F>,
+}
+
+/// A blocking task completes the first time it runs, so it is never woken
+/// and never needs to be scheduled again
+struct NoSchedule;
+
+// ===== impl BlockingPool =====
+
+impl BlockingPool {
+    pub fn new(builder: &Builder) -> BlockingPool {
+        let (tx, rx) = channel::unbounded();
+        BlockingPool {
+            inner: Arc::new(Inner {
+                tx,
+                rx,
+                threads: Mutex::new(Threads::default()),
+                max_threads: builder.max_blocking_threads,
+                keep_alive: builder.blocking_keep_alive,
+                thread_name: format!("{}-blocking", builder.thread_name),
+                on_thread_start: builder.on_thread_start.clone(),
+                on_thread_stop: builder.on_thread_stop.clone(),
+            }),
+        }
+    }
+
+    /// Runs the closure on the pool as a task. Its output, or the panic if
+    /// it panics, is delivered through the returned `JoinHandle` just like
+    /// a spawned future's
+    pub fn spawn_task<F, T>(&self, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let future = BlockingTask { func: Some(f) };
+        let (task, join_handle) = Task::new(future, NoSchedule);
+        tracing::debug!("Task {}: Spawned onto the blocking pool", task.id());
+
+        self.schedule(Box::new(move || task.run()))?;
+        Ok(join_handle)
+    }
+
+    fn schedule(&self, job: Job) -> io::Result<()> {
+        let threads = &mut *self.inner.threads.lock().unwrap();
+        // The receiver lives as long as the pool so this can't fail
+        let _ = self.inner.tx.send(job);
+
+        // Idle threads pick queued jobs up. Only if there are more jobs than
+        // they can take is a new thread needed
+        if self.inner.rx.len() > threads.idle && threads.total < self.inner.max_threads {
+            let inner = self.inner.clone();
+            let res = thread::Builder::new()
+                .name(self.inner.thread_name.clone())
+                .spawn(move || inner.run());
+            match res {
+                Ok(_) => threads.total += 1,
+                // One of the running threads picks the job up once it is free
+                Err(e) if threads.total > 0 => {
+                    tracing::warn!("Blocking pool: could not spawn thread: {}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// ===== impl Inner =====
+
+impl Inner {
+    fn run(&self) {
+        if let Some(f) = &self.on_thread_start {
+            f();
+        }
+        self.work();
+        if let Some(f) = &self.on_thread_stop {
+            f();
+        }
+    }
+
+    /// Runs jobs until the thread has been idle for too long
+    fn work(&self) {
+        loop {
+            self.threads.lock().unwrap().idle += 1;
+            let res = self.rx.recv_timeout(self.keep_alive);
+            let mut threads = self.threads.lock().unwrap();
+            threads.idle -= 1;
+
+            match res {
+                Ok(job) => {
+                    drop(threads);
+                    job();
+                }
+                // A job was queued for us while we timed out
+                Err(RecvTimeoutError::Timeout) if !self.rx.is_empty() => {}
+                Err(_) => {
+                    threads.total -= 1;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// ===== impl BlockingTask =====
+
+// The closure is never pinned, it is moved out to be called
+impl<F> Unpin for BlockingTask<F> {}
+
+impl<F, T> Future for BlockingTask<F>
+where
+    F: FnOnce() -> T,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let func = self
+            .func
+            .take()
+            .expect("blocking task polled after completion");
+        Poll::Ready(func())
+    }
+}
+
+impl Schedule for NoSchedule {
+    fn schedule(&self, _task: Task) {
+        unreachable!("blocking tasks are never rescheduled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::task::spawn_blocking;
+    use crate::time::{sleep, timeout};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn spawn_blocking_output_and_panic() {
+        let rt = Builder::new_current_thread()
+            .max_blocking_threads(1)
+            .build()
+            .unwrap();
+        let res = rt.block_on(async {
+            crate::spawn(async {
+                let slow = spawn_blocking(|| {
+                    thread::sleep(Duration::from_millis(20));
+                    thread::current().name().map(String::from)
+                });
+                let panicked = spawn_blocking(|| panic!("boom"));
+
+                // With a single thread the second closure waits for the first
+                let name = slow.await.unwrap();
+                assert_eq!(name.as_deref(), Some("woi-blocking"));
+                panicked.await
+            })
+            .await
+            .unwrap()
+        });
+
+        match res {
+            Err(crate::task::JoinError::Panic(panic)) => {
+                assert_eq!(panic.downcast_ref::<&str>(), Some(&"boom"))
+            }
+            _ => panic!("expected a panic"),
+        }
+    }
+
+    #[test]
+    fn spawn_after_threads_timed_out() {
+        let rt = Builder::new_current_thread()
+            .max_blocking_threads(1)
+            .blocking_keep_alive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // The second closure is queued while the only thread is busy
+            let first = spawn_blocking(|| thread::sleep(Duration::from_millis(10)));
+            let second = spawn_blocking(|| ());
+            first.await.unwrap();
+            second.await.unwrap();
+
+            // Once the thread has shut down, a new one is needed
+            sleep(Duration::from_millis(150)).await;
+            timeout(Duration::from_secs(2), spawn_blocking(|| ()))
+                .await
+                .expect("blocking closure never ran")
+                .unwrap();
+        });
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::Runtime;
+
+/// Called on a thread started by the runtime
+pub(crate) type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// Configures and creates a [`Runtime`]
+pub struct Builder {
+    pub(super) kind: Kind,
+    /// Number of worker threads of a multi-threaded runtime
+    pub(super) worker_threads: usize,
+    /// How many events the reactor takes from epoll at a time
+    pub(super) event_capacity: usize,
+    /// Initial capacity of the run queue
+    pub(super) queue_capacity: usize,
+    /// How many tasks run before the reactor is polled again
+    pub(super) tasks_per_tick: u32,
+    pub(super) enable_io: bool,
+    pub(super) enable_time: bool,
+    /// Whether the clock starts out paused
+    pub(super) start_paused: bool,
+    pub(super) max_blocking_threads: usize,
+    pub(super) blocking_keep_alive: Duration,
+    /// Prefix of the names of the threads started by the runtime
+    pub(super) thread_name: String,
+    pub(super) on_thread_start: Option<Callback>,
+    pub(super) on_thread_stop: Option<Callback>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    CurrentThread,
+    MultiThread,
+}
+
+impl Builder {
+    /// Configures a runtime that runs every task on the thread calling
+    /// [`block_on`](Runtime::block_on), like [`Runtime::new`]
+    pub fn new_current_thread() -> Builder {
+        Builder::new(Kind::CurrentThread)
+    }
+
+    /// Configures a runtime that runs tasks on a pool of worker threads,
+    /// like [`Runtime::new_multi_thread`]. Defaults to one worker per CPU
+    pub fn new_multi_thread() -> Builder {
+        Builder::new(Kind::MultiThread)
+    }
+
+    fn new(kind: Kind) -> Builder {
+        Builder {
+            kind,
+            worker_threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            event_capacity: 1024,
+            queue_capacity: 0,
+            tasks_per_tick: 61,
+            enable_io: true,
+            enable_time: true,
+            start_paused: false,
+            max_blocking_threads: 512,
+            blocking_keep_alive: Duration::from_secs(10),
+            thread_name: "woi".into(),
+            on_thread_start: None,
+            on_thread_stop: None,
+        }
+    }
+
+    /// Sets the number of worker threads. Only used by a multi-threaded
+    /// runtime
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero
+    pub fn worker_threads(&mut self, workers: usize) -> &mut Builder {
+        assert!(workers > 0, "a thread pool needs at least one worker");
+        self.worker_threads = workers;
+        self
+    }
+
+    /// Sets how many events the reactor takes from epoll in one go.
+    /// Defaults to 1024
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero
+    pub fn event_capacity(&mut self, capacity: usize) -> &mut Builder {
+        assert!(
+            capacity > 0,
+            "the reactor needs room for at least one event"
+        );
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Sets how many tasks the run queue has room for before it grows.
+    /// Only used by a current-thread runtime
+    pub fn queue_capacity(&mut self, capacity: usize) -> &mut Builder {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    /// Sets how many tasks run in a row before the reactor is checked for
+    /// IO and timers again. A lower value lets IO through sooner when there
+    /// are lots of tasks, at the cost of more calls to `epoll_wait`.
+    /// Defaults to 61
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tasks` is zero
+    pub fn tasks_per_tick(&mut self, tasks: u32) -> &mut Builder {
+        assert!(tasks > 0, "at least one task has to run per tick");
+        self.tasks_per_tick = tasks;
+        self
+    }
+
+    /// Enables or disables IO resources, such as sockets, on the runtime.
+    /// Creating one fails when disabled. Enabled by default
+    pub fn enable_io(&mut self, enable: bool) -> &mut Builder {
+        self.enable_io = enable;
+        self
+    }
+
+    /// Enables or disables timers on the runtime. Creating a sleep or an
+    /// interval panics when disabled, or fails for the `try_` versions.
+    /// Enabled by default
+    pub fn enable_time(&mut self, enable: bool) -> &mut Builder {
+        self.enable_time = enable;
+        self
+    }
+
+    /// Starts the runtime with its clock paused, so time only moves when it
+    /// is [advanced](crate::time::advance) or when every task is waiting on
+    /// a timer. Only a current-thread runtime can be paused. Disabled by
+    /// default
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn start_paused(&mut self, paused: bool) -> &mut Builder {
+        self.start_paused = paused;
+        self
+    }
+
+    /// Sets the upper bound on the number of threads running closures from
+    /// [`spawn_blocking`](crate::task::spawn_blocking). Beyond that, they
+    /// wait for a thread to free up. Defaults to 512
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero
+    pub fn max_blocking_threads(&mut self, max: usize) -> &mut Builder {
+        assert!(max > 0, "blocking pool needs at least one thread");
+        self.max_blocking_threads = max;
+        self
+    }
+
+    /// Sets how long a blocking thread waits for new work before shutting
+    /// down. Defaults to 10 seconds
+    pub fn blocking_keep_alive(&mut self, keep_alive: Duration) -> &mut Builder {
+        self.blocking_keep_alive = keep_alive;
+        self
+    }
+
+    /// Sets the prefix of the names of the threads started by the runtime.
+    /// Worker threads are named `{name}-worker-{index}` and blocking
+    /// threads `{name}-blocking`. Defaults to `woi`
+    pub fn thread_name(&mut self, name: impl Into<String>) -> &mut Builder {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Sets a function called on every thread started by the runtime,
+    /// before it runs anything
+    pub fn on_thread_start<F>(&mut self, f: F) -> &mut Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a function called on every thread started by the runtime,
+    /// right before it exits
+    pub fn on_thread_stop<F>(&mut self, f: F) -> &mut Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// Creates the runtime. Fails if the reactor can't be set up or, for a
+    /// multi-threaded runtime, if the worker threads can't be started or
+    /// the clock is set to start paused
+    pub fn build(&mut self) -> io::Result<Runtime> {
+        match self.kind {
+            Kind::CurrentThread => Runtime::current_thread(self),
+            Kind::MultiThread if self.start_paused => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "time can only be paused on a current-thread runtime",
+            )),
+            Kind::MultiThread => Runtime::multi_thread(self),
+        }
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("multi_thread", &(self.kind == Kind::MultiThread))
+            .field("worker_threads", &self.worker_threads)
+            .field("event_capacity", &self.event_capacity)
+            .field("queue_capacity", &self.queue_capacity)
+            .field("tasks_per_tick", &self.tasks_per_tick)
+            .field("enable_io", &self.enable_io)
+            .field("enable_time", &self.enable_time)
+            .field("start_paused", &self.start_paused)
+            .field("max_blocking_threads", &self.max_blocking_threads)
+            .field("blocking_keep_alive", &self.blocking_keep_alive)
+            .field("thread_name", &self.thread_name)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::TcpListener;
+    use crate::task::spawn_blocking;
+    use crate::time::{try_interval, try_sleep};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
+
+    #[test]
+    fn thread_names_and_hooks() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let (on_start, (stop_tx, stop_rx)) = (started.clone(), mpsc::channel());
+        let stop_tx = Mutex::new(stop_tx);
+
+        let rt = Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("custom")
+            .blocking_keep_alive(Duration::from_millis(10))
+            .on_thread_start(move || {
+                on_start.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_thread_stop(move || {
+                let _ = stop_tx.lock().unwrap().send(());
+            })
+            .build()
+            .unwrap();
+
+        let (worker, blocking) = rt.block_on(async {
+            let name = || thread::current().name().unwrap().to_string();
+            let worker = crate::spawn(async move { name() }).await.unwrap();
+            let blocking = spawn_blocking(name).await.unwrap();
+            (worker, blocking)
+        });
+        assert!(worker.starts_with("custom-worker-"));
+        assert_eq!(blocking, "custom-blocking");
+
+        // Worker threads are joined on drop, but the blocking thread
+        // outlives the runtime until it times out, so its stop is waited for
+        drop(rt);
+        let started = started.load(Ordering::SeqCst);
+        assert_eq!(started, 3);
+        for _ in 0..started {
+            stop_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("thread never stopped");
+        }
+        assert!(stop_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn start_paused_only_on_current_thread() {
+        let err = Builder::new_multi_thread()
+            .start_paused(true)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn disable_io_and_time() {
+        let rt = Builder::new_current_thread()
+            .event_capacity(1)
+            .queue_capacity(16)
+            .tasks_per_tick(1)
+            .enable_io(false)
+            .enable_time(false)
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+            assert!(TcpListener::bind(addr).await.is_err());
+            assert!(try_sleep(Duration::from_millis(1)).is_err());
+            assert!(try_interval(Duration::from_millis(1)).is_err());
+
+            // Tasks still run, one per tick
+            let handles: Vec<_> = (0..3).map(|i| crate::spawn(async move { i })).collect();
+            for (i, handle) in handles.into_iter().enumerate() {
+                assert_eq!(handle.await.unwrap(), i);
+            }
+        });
+    }
+}
@@ -1,11 +1,15 @@
 use std::cell::RefCell;
+use std::io;
 
+use super::blocking::BlockingPool;
 use super::runtime::Handle;
 use super::runtime::Spawner;
 use crate::io::reactor::Handle as IoHandle;
+use crate::time::wheel::Handle as TimeHandle;
+use crate::time::Clock;
 
 thread_local! {
-    static CONTEXT: RefCell<Option<Handle>> = RefCell::new(None)
+    static CONTEXT: RefCell<Option<Handle>> = const { RefCell::new(None) }
 }
 
 pub(crate) struct EnterGuard;
@@ -44,6 +48,18 @@ pub(crate) fn io() -> IoHandle {
     }
 }
 
+/// Whether IO resources can be created on the current runtime
+pub(crate) fn io_enabled() -> bool {
+    match CONTEXT.try_with(|ctx| {
+        let ctx = ctx.borrow();
+        let handle = ctx.as_ref().expect("No reactor running");
+        handle.enable_io
+    }) {
+        Ok(enabled) => enabled,
+        Err(_) => panic!("Thread local destroyed"),
+    }
+}
+
 pub(crate) fn spawner() -> Spawner {
     match CONTEXT.try_with(|ctx| {
         let ctx = ctx.borrow();
@@ -55,3 +71,49 @@ pub(crate) fn spawner() -> Spawner {
         Err(_) => panic!("Thread local destroyed"),
     }
 }
+
+pub(crate) fn blocking() -> BlockingPool {
+    match CONTEXT.try_with(|ctx| {
+        let ctx = ctx.borrow();
+        ctx.as_ref()
+            .map(|handle| handle.blocking.clone())
+            .expect("No reactor running")
+    }) {
+        Ok(blocking) => blocking,
+        Err(_) => panic!("Thread local destroyed"),
+    }
+}
+
+pub(crate) fn time() -> TimeHandle {
+    match try_time() {
+        Ok(time) => time,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Fails outside of a runtime or if timers are disabled on it
+pub(crate) fn try_time() -> io::Result<TimeHandle> {
+    let res = CONTEXT.try_with(|ctx| match ctx.borrow().as_ref() {
+        Some(handle) if handle.enable_time => Ok(handle.time.clone()),
+        Some(_) => Err(io::Error::other("Timers are disabled on this runtime")),
+        None => Err(io::Error::other("No reactor running")),
+    });
+    match res {
+        Ok(res) => res,
+        Err(_) => panic!("Thread local destroyed"),
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn clock() -> Clock {
+    try_clock().expect("No reactor running")
+}
+
+/// Unlike the other accessors this doesn't panic outside of a runtime,
+/// since [`Instant::now`](crate::time::Instant::now) works anywhere
+pub(crate) fn try_clock() -> Option<Clock> {
+    CONTEXT
+        .try_with(|ctx| ctx.borrow().as_ref().map(|handle| handle.clock.clone()))
+        .ok()
+        .flatten()
+}
@@ -1,4 +1,14 @@
+pub(crate) mod blocking;
+
+mod builder;
+pub use builder::Builder;
+
 pub(crate) mod context;
+mod owned;
+mod queue;
 
+#[allow(clippy::module_inception)]
 mod runtime;
 pub use runtime::Runtime;
+
+mod thread_pool;
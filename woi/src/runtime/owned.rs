@@ -0,0 +1,111 @@
+//! Registry of the tasks spawned onto a runtime
+//!
+//! A task that is parked on IO or a timer isn't in any queue, so without
+//! this the runtime would have no way of reaching it when it shuts down.
+//! Each entry holds an [`AbortHandle`], which keeps the task alive until it
+//! completes and lets the runtime cancel it.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures::future::poll_fn;
+
+use crate::task::header::TaskId;
+use crate::task::AbortHandle;
+
+#[derive(Clone)]
+pub(crate) struct OwnedTasks {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    tasks: HashMap<TaskId, AbortHandle>,
+    /// Set once the runtime shuts down. Tasks spawned after that are
+    /// cancelled straight away
+    closed: bool,
+    /// Woken once the last task is released
+    waiter: Option<Waker>,
+}
+
+impl OwnedTasks {
+    pub fn new() -> OwnedTasks {
+        OwnedTasks {
+            inner: Arc::new(Mutex::new(Inner {
+                tasks: HashMap::new(),
+                closed: false,
+                waiter: None,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // Nothing panics while holding the lock, so it can't be poisoned
+        self.inner.lock().unwrap()
+    }
+
+    /// Starts tracking a newly spawned task. If the runtime is shutting
+    /// down, the task is aborted instead and false is returned, in which
+    /// case the caller runs it so its future is dropped
+    pub fn bind(&self, id: TaskId, handle: AbortHandle) -> bool {
+        let mut inner = self.lock();
+        if inner.closed {
+            drop(inner);
+            handle.abort();
+            return false;
+        }
+
+        inner.tasks.insert(id, handle);
+        true
+    }
+
+    /// Stops tracking a completed task
+    pub fn release(&self, id: TaskId) {
+        let mut inner = self.lock();
+        // Dropped outside of the lock, since it may be the task's last
+        // reference
+        let handle = inner.tasks.remove(&id);
+        let waiter = if inner.tasks.is_empty() {
+            inner.waiter.take()
+        } else {
+            None
+        };
+        drop(inner);
+
+        drop(handle);
+        if let Some(waiter) = waiter {
+            waiter.wake();
+        }
+    }
+
+    /// Stops new tasks from being spawned and aborts every task that is
+    /// still around. Each one drops its future the next time it runs
+    pub fn close_and_abort_all(&self) {
+        let handles: Vec<_> = {
+            let mut inner = self.lock();
+            inner.closed = true;
+            inner.tasks.values().cloned().collect()
+        };
+
+        tracing::debug!("Aborting {} tasks", handles.len());
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
+    /// Completes once every task has been released
+    pub fn wait_empty(&self) -> impl Future<Output = ()> + '_ {
+        poll_fn(move |cx| self.poll_empty(cx))
+    }
+
+    fn poll_empty(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut inner = self.lock();
+        if inner.tasks.is_empty() {
+            return Poll::Ready(());
+        }
+
+        inner.waiter = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,208 @@
+//! Run queue of the current-thread runtime
+//!
+//! Tasks are only ever run by the thread that owns the runtime. Tasks
+//! scheduled on that thread go onto a local queue, which needs no locking.
+//! Wakers can also be called from other threads, e.g. by a callback library
+//! or a blocking thread. Those tasks go onto a remote queue instead, and the
+//! reactor is unparked so a runtime waiting in `epoll_wait` picks them up.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use super::owned::OwnedTasks;
+use crate::io::reactor::Handle as IoHandle;
+use crate::task::header::TaskId;
+use crate::task::raw::Schedule;
+use crate::task::Task;
+
+/// Handle to the run queue. Tasks can be scheduled through it from any
+/// thread
+#[derive(Clone)]
+pub(crate) struct Queue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Tasks scheduled by the owner
+    local: LocalQueue,
+    /// Tasks scheduled from other threads
+    remote: Mutex<VecDeque<Task>>,
+    /// Set while the remote queue has tasks, so the owner only takes the
+    /// lock when there is something to take
+    has_remote: AtomicBool,
+    /// Used to interrupt the owner when it is parked on the reactor
+    io: IoHandle,
+    /// Every task spawned onto the runtime that hasn't completed
+    owned: OwnedTasks,
+}
+
+/// Queue that only the thread owning the runtime may touch. Other threads
+/// panic if they try
+struct LocalQueue {
+    /// The thread that owns the runtime
+    owner: ThreadId,
+    tasks: RefCell<VecDeque<Task>>,
+}
+
+// SAFETY: the tasks are only reached through `LocalQueue::with`, which
+// panics on any thread but the owner, so the `RefCell` is never borrowed by
+// two threads at once. Tasks spawned with `spawn_local` can hold futures
+// that aren't `Send`, so they must not be dropped elsewhere either. The
+// queue itself is dropped by whichever thread drops the last `Queue`, which
+// is why `Drop` leaks the tasks when that isn't the owner
+unsafe impl Sync for LocalQueue {}
+
+impl Queue {
+    /// Creates a queue owned by the current thread
+    pub fn new(io: IoHandle, capacity: usize) -> Queue {
+        Queue {
+            inner: Arc::new(Inner {
+                local: LocalQueue {
+                    owner: thread::current().id(),
+                    tasks: RefCell::new(VecDeque::with_capacity(capacity)),
+                },
+                remote: Mutex::new(VecDeque::new()),
+                has_remote: AtomicBool::new(false),
+                io,
+                owned: OwnedTasks::new(),
+            }),
+        }
+    }
+
+    pub fn owned(&self) -> &OwnedTasks {
+        &self.inner.owned
+    }
+
+    /// Takes the next task to run. Tasks from other threads are moved over
+    /// once the local queue is empty
+    ///
+    /// Must only be called by the owner
+    pub fn pop(&self) -> Option<Task> {
+        self.inner.local.with(|local| {
+            if local.is_empty() && self.inner.has_remote.swap(false, Ordering::AcqRel) {
+                local.append(&mut self.inner.remote.lock().unwrap());
+            }
+            local.pop_front()
+        })
+    }
+
+    /// Must only be called by the owner
+    pub fn is_empty(&self) -> bool {
+        self.inner.local.with(|local| local.is_empty())
+            && !self.inner.has_remote.load(Ordering::Acquire)
+    }
+}
+
+impl Schedule for Queue {
+    fn schedule(&self, task: Task) {
+        if self.inner.local.is_owner() {
+            self.inner.local.with(|local| local.push_back(task));
+            return;
+        }
+
+        tracing::debug!("Task {}: Scheduled from another thread", task.id());
+        self.inner.remote.lock().unwrap().push_back(task);
+        self.inner.has_remote.store(true, Ordering::Release);
+        self.inner.io.unpark();
+    }
+
+    fn release(&self, id: TaskId) {
+        self.inner.owned.release(id);
+    }
+}
+
+// ===== impl LocalQueue =====
+
+impl LocalQueue {
+    fn is_owner(&self) -> bool {
+        thread::current().id() == self.owner
+    }
+
+    /// Gives access to the tasks
+    ///
+    /// # Panics
+    ///
+    /// Panics if called by any thread but the owner
+    fn with<R>(&self, f: impl FnOnce(&mut VecDeque<Task>) -> R) -> R {
+        assert!(
+            self.is_owner(),
+            "local run queue accessed from a thread that doesn't own the runtime"
+        );
+        f(&mut self.tasks.borrow_mut())
+    }
+}
+
+impl Drop for LocalQueue {
+    fn drop(&mut self) {
+        if self.is_owner() {
+            return;
+        }
+
+        // The tasks' futures may not be `Send`, so they can't be dropped on
+        // this thread. Leaking them is all that's left
+        let tasks = mem::take(self.tasks.get_mut());
+        if !tasks.is_empty() {
+            tracing::warn!("Leaking {} tasks dropped off the runtime's thread", tasks.len());
+        }
+        mem::forget(tasks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::reactor::Reactor;
+    use crate::test_util::DropFlag;
+    use crate::time::timeout;
+    use crate::Runtime;
+    use futures::channel::oneshot;
+    use std::sync::atomic::AtomicBool;
+    use std::time::{Duration, Instant};
+
+    struct NoSchedule;
+
+    impl Schedule for NoSchedule {
+        fn schedule(&self, _: Task) {}
+    }
+
+    #[test]
+    fn wake_from_another_thread() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (tx, rx) = oneshot::channel();
+            let task = crate::spawn(rx);
+
+            let start = Instant::now();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                tx.send(42).unwrap();
+            });
+
+            // The runtime is parked in `epoll_wait` with nothing else to do,
+            // so only the eventfd can wake it before the timeout
+            let res = timeout(Duration::from_secs(5), task).await;
+            assert_eq!(res.unwrap().unwrap(), Ok(42));
+            assert!(start.elapsed() < Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn leak_tasks_dropped_off_owner() {
+        let reactor = Reactor::new(8).unwrap();
+        let queue = Queue::new(reactor.handle(), 8);
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let (task, join_handle) = Task::new(async move { drop(flag) }, NoSchedule);
+        drop(join_handle);
+        queue.schedule(task);
+
+        // The task's future could be `!Send`, so it must not be dropped here
+        thread::spawn(move || drop(queue)).join().unwrap();
+        assert!(!dropped.load(Ordering::SeqCst));
+    }
+}
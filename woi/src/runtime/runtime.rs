@@ -1,28 +1,58 @@
 use std::cell::RefCell;
-use std::collections::VecDeque;
 use std::future::Future;
+use std::io;
 use std::marker::PhantomData;
-use std::rc::Rc;
-use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
 
+use futures::task::ArcWake;
+
+use super::blocking::BlockingPool;
+use super::builder::Builder;
 use super::context;
+use super::owned::OwnedTasks;
+use super::queue::Queue;
+use super::thread_pool::{self, ThreadPool};
 use crate::io::reactor::{Handle as IoHandle, Reactor};
+use crate::task::coop;
 use crate::task::join::JoinHandle;
-use crate::task::raw::{RawTask, Schedule};
+use crate::task::raw::Schedule;
 use crate::task::Task;
+use crate::time::wheel::Handle as TimeHandle;
+use crate::time::{Clock, Timeout};
 
 pub struct Runtime {
-    // Holds the reactor and task queue
-    inner: RefCell<Inner>,
+    // How tasks are run
+    kind: Kind,
     // Handle to runtime
     handle: Handle,
 }
 
+enum Kind {
+    /// Holds the reactor and task queue. Every task runs on the thread
+    /// calling `block_on`
+    CurrentThread(RefCell<Inner>),
+    /// Tasks run on a pool of worker threads
+    MultiThread(ThreadPool),
+}
+
 struct Inner {
     /// IO reactor
     reactor: Reactor,
+    /// Timer wheel
+    time: TimeHandle,
+    /// Source of time, which can be paused
+    clock: Clock,
     /// Queue that holds tasks
     queue: Queue,
+    /// How many tasks run before the reactor is checked again
+    tasks_per_tick: u32,
+    /// Tasks spawned with `spawn_local` can't be moved to another thread,
+    /// so neither can the runtime
+    _not_send: PhantomData<*const ()>,
 }
 
 /// Handle to the runtime
@@ -32,36 +62,115 @@ pub struct Handle {
     pub(crate) spawner: Spawner,
     /// Handle to the IO reactor
     pub(crate) io: IoHandle,
+    /// Pool of threads for running blocking work
+    pub(crate) blocking: BlockingPool,
+    /// Handle to the timer wheel
+    pub(crate) time: TimeHandle,
+    /// The runtime's clock
+    pub(crate) clock: Clock,
+    /// Whether IO resources can be created
+    pub(crate) enable_io: bool,
+    /// Whether timers can be created
+    pub(crate) enable_time: bool,
 }
 
 #[derive(Clone)]
-pub struct Spawner {
-    queue: Queue,
+pub enum Spawner {
+    CurrentThread(Queue),
+    MultiThread(thread_pool::Spawner),
 }
 
-type Queue = Rc<RefCell<VecDeque<Task>>>;
-
 // ===== impl Runtime =====
 
 impl Runtime {
+    /// Creates a runtime that runs every task on the thread calling
+    /// [`block_on`](Runtime::block_on). Tasks don't have to be `Send` if
+    /// they are spawned with [`spawn_local`](crate::spawn_local). Use a
+    /// [`Builder`] to configure it
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reactor can't be set up
     pub fn new() -> Runtime {
-        let queue = Rc::new(RefCell::new(VecDeque::new()));
-        let spawner = Spawner {
-            queue: queue.clone(),
-        };
+        Builder::new_current_thread()
+            .build()
+            .expect("Could not start reactor!")
+    }
 
-        let reactor = Reactor::new().expect("Could not start reactor!");
+    /// Creates a runtime that runs tasks on `workers` threads. Idle workers
+    /// steal tasks from busy ones, and any of them can wait on the reactor
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero, or if the reactor or worker threads
+    /// can't be started
+    pub fn new_multi_thread(workers: usize) -> Runtime {
+        Builder::new_multi_thread()
+            .worker_threads(workers)
+            .build()
+            .expect("Could not start runtime!")
+    }
+
+    pub(super) fn current_thread(builder: &Builder) -> io::Result<Runtime> {
+        let reactor = Reactor::new(builder.event_capacity)?;
         let io_handle = reactor.handle();
 
+        let queue = Queue::new(io_handle.clone(), builder.queue_capacity);
+        let spawner = Spawner::CurrentThread(queue.clone());
+        let time = TimeHandle::new(io_handle.clone());
+        let clock = Clock::new();
+        if builder.start_paused {
+            clock.pause(&time);
+        }
+
         // Runtime handle
         let handle = Handle {
             spawner,
             io: io_handle,
+            blocking: BlockingPool::new(builder),
+            time: time.clone(),
+            clock: clock.clone(),
+            enable_io: builder.enable_io,
+            enable_time: builder.enable_time,
         };
 
-        let inner = RefCell::new(Inner { reactor, queue });
+        let inner = RefCell::new(Inner {
+            reactor,
+            time,
+            clock,
+            queue,
+            tasks_per_tick: builder.tasks_per_tick,
+            _not_send: PhantomData,
+        });
+
+        Ok(Runtime {
+            kind: Kind::CurrentThread(inner),
+            handle,
+        })
+    }
+
+    pub(super) fn multi_thread(builder: &Builder) -> io::Result<Runtime> {
+        let reactor = Reactor::new(builder.event_capacity)?;
+        let io_handle = reactor.handle();
+        let time = TimeHandle::new(io_handle.clone());
+
+        let mut pool = ThreadPool::new(builder, reactor, time.clone());
+        let handle = Handle {
+            spawner: Spawner::MultiThread(pool.spawner()),
+            io: io_handle,
+            blocking: BlockingPool::new(builder),
+            time,
+            clock: Clock::new(),
+            enable_io: builder.enable_io,
+            enable_time: builder.enable_time,
+        };
+        // Workers that did start are stopped when the pool is dropped
+        pool.launch(&handle)?;
 
-        Runtime { inner, handle }
+        Ok(Runtime {
+            kind: Kind::MultiThread(pool),
+            handle,
+        })
     }
 
     // Get the handle to the runtime
@@ -69,15 +178,76 @@ impl Runtime {
         &self.handle
     }
 
-    // Spawn a task onto the runtime
-    pub fn spawn<F: Future>(&self, future: F) -> JoinHandle<F::Output> {
+    // Spawn a task onto the runtime. The future must be `Send`, see
+    // `spawn_local` for ones that aren't
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
         self.handle.spawn(future)
     }
 
+    /// Spawns a task that doesn't have to be `Send` onto a current-thread
+    /// runtime. Like [`crate::spawn_local`], but callable from outside
+    /// [`block_on`](Runtime::block_on). The runtime can't leave the thread
+    /// that created it, so neither can the task
+    ///
+    /// # Panics
+    ///
+    /// Panics on a multi-threaded runtime
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.handle.spawner.spawn_local(future)
+    }
+
+    /// Runs the future to completion on the calling thread. The future
+    /// doesn't have to be `Send`
     pub fn block_on<F: Future>(&self, future: F) -> F::Output {
         // Enter runtime context
         let _enter = context::enter(self.handle.clone());
-        self.inner.borrow_mut().block_on(future)
+        match &self.kind {
+            Kind::CurrentThread(inner) => inner.borrow_mut().block_on(future),
+            Kind::MultiThread(pool) => pool.block_on(future),
+        }
+    }
+
+    /// Shuts the runtime down, giving its tasks up to `timeout` to
+    /// complete first. Tasks that are still around after that are
+    /// cancelled, as they are when the runtime is simply dropped: their
+    /// futures are dropped and their `JoinHandle`s resolve to
+    /// [`JoinError::Cancelled`](crate::task::JoinError::Cancelled). IO
+    /// resources that outlive the runtime fail from then on
+    pub fn shutdown_timeout(self, timeout: Duration) {
+        let owned = self.handle.spawner.owned().clone();
+        // The timer wheel is driven even if timers are disabled for tasks,
+        // so the deadline goes on it directly
+        let deadline = crate::time::Instant::after(timeout);
+        let time = self.handle.time.clone();
+        self.block_on(async {
+            // Whatever didn't complete in time is cancelled on drop
+            let _ = Timeout::with_handle(time, deadline, owned.wait_empty()).await;
+        });
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        tracing::debug!("Shutting down runtime");
+        // Dropping futures may need the runtime, e.g. to remove a timer
+        let _enter = context::enter(self.handle.clone());
+        match &mut self.kind {
+            Kind::CurrentThread(inner) => inner.get_mut().shutdown(),
+            Kind::MultiThread(pool) => pool.shutdown(),
+        }
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Runtime {
+        Runtime::new()
     }
 }
 
@@ -87,14 +257,20 @@ impl Inner {
     pub fn block_on<F: Future>(&mut self, future: F) -> F::Output {
         crate::pin!(future);
 
-        let waker = unsafe { Waker::from_raw(NoopWaker::waker()) };
+        let root = Arc::new(RootWaker::new(self.reactor.handle()));
+        let waker = futures::task::waker(root.clone());
         let cx = &mut Context::from_waker(&waker);
 
         loop {
-            // If the future is ready, return the output
-            tracing::debug!("Polling `block_on` future");
-            if let Poll::Ready(v) = future.as_mut().poll(cx) {
-                return v;
+            // The future starts out woken so it is polled once. After that it
+            // is only polled again when it has been woken. If it is ready,
+            // return the output. Any wake up from before this poll is taken
+            // care of by it
+            if root.take_woken() {
+                tracing::debug!("Polling `block_on` future");
+                if let Poll::Ready(v) = coop::budget(|| future.as_mut().poll(cx)) {
+                    return v;
+                }
             }
 
             // Since we're here, we know the 'block_on' future isn't ready. We then
@@ -104,19 +280,28 @@ impl Inner {
             //    Essentially, this means we have events registered in our reactor and
             //    we are waiting for them to fire.
             // 2. If there are tasks spawned onto the runtime, we can start processing them
-            if self.queue.borrow().is_empty() {
-                tracing::debug!("Parking on epoll");
+            //
+            // Timers that are due are fired first since they may schedule tasks.
+            // When parking, we wake up in time for the nearest timer. If the
+            // `block_on` future woke itself, there is work to do and we don't park.
+            // IO that became ready is still picked up without blocking, so a
+            // busy runtime doesn't starve it
+            self.time.process();
+            if self.queue.is_empty() && !root.is_woken() {
+                self.park();
+            } else {
                 self.reactor
-                    .react(None)
+                    .react(Some(Duration::ZERO))
                     .expect("Reactor failed to process events");
             }
-
-            // We have tasks to process. We process all of them. After, we proceed to
-            // to poll the outer future again with the hope that we aren't waiting on
-            // anymore resources and are now finished our work (unless we are a web
-            // server of course)
-            loop {
-                let task = self.queue.borrow_mut().pop_front();
+            self.time.process();
+
+            // We have tasks to process. We process up to a tick's worth of them.
+            // After, we proceed to poll the outer future again with the hope that
+            // we aren't waiting on anymore resources and are now finished our work
+            // (unless we are a web server of course)
+            for _ in 0..self.tasks_per_tick {
+                let task = self.queue.pop();
                 match task {
                     Some(task) => {
                         tracing::debug!(
@@ -130,12 +315,53 @@ impl Inner {
             }
         }
     }
+
+    /// Cancels every task, dropping their futures on this thread, and then
+    /// shuts down the reactor
+    fn shutdown(&mut self) {
+        self.queue.owned().close_and_abort_all();
+        while let Some(task) = self.queue.pop() {
+            task.run();
+        }
+
+        self.reactor.shutdown();
+    }
+
+    /// Waits on the reactor for IO or the nearest timer. With the clock
+    /// paused, there is no point waiting for a timer. Instead, if no IO is
+    /// ready, time jumps forward to the nearest timer
+    fn park(&mut self) {
+        if self.clock.is_paused() {
+            let events = self
+                .reactor
+                .react(Some(Duration::ZERO))
+                .expect("Reactor failed to process events");
+            if events > 0 {
+                return;
+            }
+
+            if let Some(deadline) = self.time.next_deadline() {
+                tracing::debug!("Clock paused: advancing to the next timer");
+                self.clock.advance_to(deadline);
+                return;
+            }
+        }
+
+        tracing::debug!("Parking on epoll");
+        self.reactor
+            .react(self.time.next_timeout())
+            .expect("Reactor failed to process events");
+    }
 }
 
 // ===== impl Handle =====
 
 impl Handle {
-    pub fn spawn<F: Future>(&self, future: F) -> JoinHandle<F::Output> {
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
         self.spawner.spawn(future)
     }
 }
@@ -143,41 +369,236 @@ impl Handle {
 // ===== impl Spawner =====
 
 impl Spawner {
-    pub fn spawn<F: Future>(&self, future: F) -> JoinHandle<F::Output> {
-        let raw = RawTask::new(future, self.queue.clone());
-        let task = Task { raw };
-        let join_handle = JoinHandle {
-            raw,
-            _marker: PhantomData,
-        };
-        tracing::debug!("Task {}: Spawned", task.id());
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match self {
+            Spawner::CurrentThread(queue) => spawn_local(queue, future),
+            Spawner::MultiThread(spawner) => spawner.spawn(future),
+        }
+    }
 
-        self.queue.schedule(task);
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        match self {
+            Spawner::CurrentThread(queue) => spawn_local(queue, future),
+            Spawner::MultiThread(_) => {
+                panic!("`spawn_local` can only be used on a current-thread runtime")
+            }
+        }
+    }
 
-        join_handle
+    fn owned(&self) -> &OwnedTasks {
+        match self {
+            Spawner::CurrentThread(queue) => queue.owned(),
+            Spawner::MultiThread(spawner) => spawner.owned(),
+        }
     }
 }
 
-// ===== impl Queue =====
+fn spawn_local<F: Future>(queue: &Queue, future: F) -> JoinHandle<F::Output> {
+    let (task, join_handle) = Task::new(future, queue.clone());
+    tracing::debug!("Task {}: Spawned", task.id());
 
-impl Schedule for Queue {
-    fn schedule(&self, task: Task) {
-        self.borrow_mut().push_back(task);
+    if queue.owned().bind(task.id(), join_handle.abort_handle()) {
+        queue.schedule(task);
+    } else {
+        // The runtime has shut down. The task was aborted, so running it
+        // just drops the future
+        task.run();
     }
+
+    join_handle
 }
 
-// ===== No op waker =====
+// ===== Root waker =====
+
+/// Waker for the `block_on` future. It records that the future was woken
+/// so we know not to park while it has work to do, and only poll it when
+/// it does. When woken from another thread, the runtime may be parked on
+/// the reactor, so it is interrupted
+struct RootWaker {
+    woken: AtomicBool,
+    /// The thread calling `block_on`
+    owner: ThreadId,
+    io: IoHandle,
+}
+
+impl RootWaker {
+    fn new(io: IoHandle) -> RootWaker {
+        RootWaker {
+            woken: AtomicBool::new(true),
+            owner: thread::current().id(),
+            io,
+        }
+    }
+
+    fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::AcqRel)
+    }
 
-struct NoopWaker;
+    fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Acquire)
+    }
+}
 
-impl NoopWaker {
-    fn waker() -> RawWaker {
-        fn no_op(_: *const ()) {}
-        fn clone(_: *const ()) -> RawWaker {
-            NoopWaker::waker()
+impl ArcWake for RootWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        let was_woken = arc_self.woken.swap(true, Ordering::AcqRel);
+        if !was_woken && thread::current().id() != arc_self.owner {
+            arc_self.io.unpark();
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::TcpListener;
+    use crate::test_util::DropFlag;
+    use crate::time::sleep;
+    use std::net::SocketAddr;
+    use std::rc::Rc;
+    use std::pin::Pin;
+
+    fn shutdown_cancels_remaining_tasks(rt: Runtime) {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+
+        let (quick, slow) = rt.block_on(async {
+            let quick = crate::spawn(async {
+                sleep(Duration::from_millis(10)).await;
+                1
+            });
+            let slow = crate::spawn(async move {
+                let _flag = flag;
+                sleep(Duration::from_secs(60)).await;
+            });
+            (quick, slow)
+        });
+
+        let start = std::time::Instant::now();
+        rt.shutdown_timeout(Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(dropped.load(Ordering::Acquire));
+
+        assert_eq!(futures::executor::block_on(quick).unwrap(), 1);
+        let err = futures::executor::block_on(slow).unwrap_err();
+        assert!(err.is_cancelled());
+    }
+
+    #[test]
+    fn shutdown_current_thread() {
+        shutdown_cancels_remaining_tasks(Runtime::new());
+    }
+
+    #[test]
+    fn shutdown_multi_thread() {
+        shutdown_cancels_remaining_tasks(Runtime::new_multi_thread(2));
+    }
+
+    #[test]
+    fn shutdown_with_time_disabled() {
+        let rt = Builder::new_current_thread()
+            .enable_time(false)
+            .build()
+            .unwrap();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let pending = rt.spawn(async move {
+            let _flag = flag;
+            futures::future::pending::<()>().await
+        });
+
+        let start = std::time::Instant::now();
+        rt.shutdown_timeout(Duration::from_millis(10));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(dropped.load(Ordering::Acquire));
+        assert!(futures::executor::block_on(pending)
+            .unwrap_err()
+            .is_cancelled());
+    }
+
+    #[test]
+    fn spawn_local_from_outside() {
+        let rt = Runtime::new();
+        let shared = Rc::new(RefCell::new(Vec::new()));
+        let task_shared = shared.clone();
+        let handle = rt.spawn_local(async move { task_shared.borrow_mut().push(1) });
+        rt.block_on(handle).unwrap();
+        assert_eq!(*shared.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn shutdown_with_max_timeout() {
+        let rt = Runtime::new();
+        let done = rt.spawn(async {});
+        rt.shutdown_timeout(Duration::MAX);
+        assert!(futures::executor::block_on(done).is_ok());
+    }
+
+    #[test]
+    fn root_woken_from_another_thread() {
+        let rt = Runtime::new();
+        let (tx, rx) = futures::channel::oneshot::channel();
+        let start = std::time::Instant::now();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(()).unwrap();
+        });
+
+        // Nothing else wakes the runtime before the timeout
+        let res = rt.block_on(async { crate::time::timeout(Duration::from_secs(5), rx).await });
+        assert!(res.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn root_only_polled_when_woken() {
+        let rt = Runtime::new();
+        let mut polls = 0;
+        rt.block_on(async {
+            let (tx, mut rx) = futures::channel::oneshot::channel();
+            crate::spawn(async move {
+                // Keeps the runtime busy without waking the root future
+                for _ in 0..10 {
+                    let mut yielded = false;
+                    futures::future::poll_fn(|cx| {
+                        if yielded {
+                            return Poll::Ready(());
+                        }
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    })
+                    .await;
+                }
+                tx.send(()).unwrap();
+            });
+
+            futures::future::poll_fn(|cx| {
+                polls += 1;
+                Pin::new(&mut rx).poll(cx)
+            })
+            .await
+            .unwrap();
+        });
+        assert_eq!(polls, 2);
+    }
+
+    #[test]
+    fn io_fails_after_shutdown() {
+        let rt = Runtime::new();
+        let listener = rt
+            .block_on(TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))))
+            .unwrap();
+        drop(rt);
 
-        let vtable = &RawWakerVTable::new(clone, no_op, no_op, no_op);
-        RawWaker::new(0 as *const (), vtable)
+        let rt = Runtime::new();
+        assert!(rt.block_on(listener.accept()).is_err());
     }
 }
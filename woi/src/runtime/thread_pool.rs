@@ -0,0 +1,527 @@
+//! Multi-threaded flavor of the runtime
+//!
+//! Every worker thread has its own run queue. Tasks spawned or woken on a
+//! worker go into its queue, while tasks scheduled from any other thread go
+//! into a shared injection queue. A worker that runs out of tasks takes a
+//! batch from the injection queue, then tries stealing from the other
+//! workers. If there is still nothing to do, it parks. The first idle worker
+//! parks on the reactor and fires timers, the others sleep until they are
+//! notified of new tasks.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::iter;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle as ThreadHandle, Thread};
+use std::time::Duration;
+
+use crossbeam::deque::{Injector, Stealer, Worker};
+use futures::task::ArcWake;
+
+use super::builder::{Builder, Callback};
+use super::context;
+use super::owned::OwnedTasks;
+use super::runtime::Handle;
+use crate::io::reactor::{Handle as IoHandle, Reactor};
+use crate::task::coop;
+use crate::task::header::TaskId;
+use crate::task::join::JoinHandle;
+use crate::task::raw::Schedule;
+use crate::task::Task;
+use crate::time::wheel::Handle as TimeHandle;
+
+/// How many tasks a worker runs before looking at the injection queue
+/// first, so tasks scheduled from outside aren't starved by busy workers
+const INJECTOR_INTERVAL: u32 = 61;
+
+pub(crate) struct ThreadPool {
+    shared: Arc<Shared>,
+    /// Run queues of the workers that haven't been launched yet
+    queues: Vec<Worker<Task>>,
+    /// Worker threads, joined on drop
+    threads: Vec<ThreadHandle<()>>,
+    thread_name: String,
+    on_thread_start: Option<Callback>,
+    on_thread_stop: Option<Callback>,
+}
+
+/// Spawns tasks onto the thread pool
+#[derive(Clone)]
+pub(crate) struct Spawner {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// Tasks scheduled from outside of the workers
+    injector: Injector<Task>,
+    /// Used to steal from the run queue of each worker
+    stealers: Vec<Stealer<Task>>,
+    /// The reactor. Whichever idle worker locks it parks on it
+    reactor: Mutex<Reactor>,
+    /// Set while a worker is parked on the reactor
+    reactor_parked: AtomicBool,
+    io: IoHandle,
+    time: TimeHandle,
+    /// How many tasks a worker runs before polling the reactor, so IO and
+    /// timers aren't starved while every worker is busy
+    reactor_interval: u32,
+    /// Held by workers going to sleep, so they can't miss a notification
+    /// between checking for tasks and waiting on the condvar
+    idle: Mutex<()>,
+    /// Notified when there are tasks for sleeping workers
+    condvar: Condvar,
+    /// Number of workers asleep on the condvar
+    sleeping: AtomicUsize,
+    /// Every task spawned onto the pool that hasn't completed
+    owned: OwnedTasks,
+    /// Set once the workers should exit
+    shutdown: AtomicBool,
+}
+
+/// State of the worker running on the current thread
+struct Local {
+    shared: Arc<Shared>,
+    /// Position of the worker's stealer in [`Shared::stealers`]
+    index: usize,
+    queue: Worker<Task>,
+}
+
+thread_local! {
+    static LOCAL: RefCell<Option<Local>> = const { RefCell::new(None) }
+}
+
+/// Waker for the `block_on` future. It runs on the calling thread, which
+/// sleeps until the future is woken
+struct ThreadWaker {
+    thread: Thread,
+}
+
+// ===== impl ThreadPool =====
+
+impl ThreadPool {
+    /// Creates a pool of workers as configured by the builder. They only
+    /// start running once the pool is launched with the runtime's handle
+    pub fn new(builder: &Builder, reactor: Reactor, time: TimeHandle) -> ThreadPool {
+        let size = builder.worker_threads;
+        assert!(size > 0, "a thread pool needs at least one worker");
+
+        let queues: Vec<_> = (0..size).map(|_| Worker::new_fifo()).collect();
+        let shared = Arc::new(Shared {
+            injector: Injector::new(),
+            stealers: queues.iter().map(Worker::stealer).collect(),
+            io: reactor.handle(),
+            reactor: Mutex::new(reactor),
+            reactor_parked: AtomicBool::new(false),
+            time,
+            reactor_interval: builder.tasks_per_tick,
+            idle: Mutex::new(()),
+            condvar: Condvar::new(),
+            sleeping: AtomicUsize::new(0),
+            owned: OwnedTasks::new(),
+            shutdown: AtomicBool::new(false),
+        });
+
+        ThreadPool {
+            shared,
+            queues,
+            threads: Vec::new(),
+            thread_name: builder.thread_name.clone(),
+            on_thread_start: builder.on_thread_start.clone(),
+            on_thread_stop: builder.on_thread_stop.clone(),
+        }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Starts the worker threads. Each one enters the runtime's context
+    pub fn launch(&mut self, handle: &Handle) -> io::Result<()> {
+        for (index, queue) in self.queues.drain(..).enumerate() {
+            let local = Local {
+                shared: self.shared.clone(),
+                index,
+                queue,
+            };
+            let handle = handle.clone();
+            let on_start = self.on_thread_start.clone();
+            let on_stop = self.on_thread_stop.clone();
+            let thread = thread::Builder::new()
+                .name(format!("{}-worker-{}", self.thread_name, index))
+                .spawn(move || {
+                    if let Some(f) = on_start {
+                        f();
+                    }
+                    let _enter = context::enter(handle);
+                    local.run();
+                    if let Some(f) = on_stop {
+                        f();
+                    }
+                })?;
+            self.threads.push(thread);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the future on the calling thread while the workers run the
+    /// tasks. The thread sleeps whenever the future is pending
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        crate::pin!(future);
+
+        let waker = futures::task::waker(Arc::new(ThreadWaker {
+            thread: thread::current(),
+        }));
+        let cx = &mut Context::from_waker(&waker);
+
+        loop {
+            if let Poll::Ready(v) = coop::budget(|| future.as_mut().poll(cx)) {
+                return v;
+            }
+
+            // Returns straight away if the future was woken since it was
+            // polled. Spurious wake ups only cost an extra poll
+            thread::park();
+        }
+    }
+
+    /// Cancels every task and waits for the workers to drop their futures.
+    /// The workers are then stopped and the reactor shut down
+    pub fn shutdown(&mut self) {
+        let owned = self.shared.owned.clone();
+        owned.close_and_abort_all();
+        self.block_on(owned.wait_empty());
+        self.stop();
+        self.shared.reactor.lock().unwrap().shutdown();
+    }
+
+    /// Stops the workers and waits for them to exit
+    fn stop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        {
+            let _idle = self.shared.idle.lock().unwrap();
+            self.shared.condvar.notify_all();
+        }
+        self.shared.io.unpark();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        tracing::debug!("Shutting down thread pool");
+        self.stop();
+    }
+}
+
+// ===== impl Spawner =====
+
+impl Spawner {
+    pub fn owned(&self) -> &OwnedTasks {
+        &self.shared.owned
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, join_handle) = Task::new(future, self.clone());
+        tracing::debug!("Task {}: Spawned", task.id());
+
+        if self
+            .shared
+            .owned
+            .bind(task.id(), join_handle.abort_handle())
+        {
+            self.schedule(task);
+        } else {
+            // The runtime has shut down. The task was aborted, so running
+            // it just drops the future
+            task.run();
+        }
+
+        join_handle
+    }
+}
+
+impl Schedule for Spawner {
+    fn schedule(&self, task: Task) {
+        // Tasks scheduled by one of our own workers go to the back of its
+        // queue. Anything else goes through the injection queue
+        let task = LOCAL
+            .try_with(|local| match &*local.borrow() {
+                Some(local) if Arc::ptr_eq(&local.shared, &self.shared) => {
+                    local.queue.push(task);
+                    None
+                }
+                _ => Some(task),
+            })
+            .unwrap_or_else(|_| panic!("Thread local destroyed"));
+
+        if let Some(task) = task {
+            self.shared.injector.push(task);
+        }
+
+        self.shared.notify();
+    }
+
+    fn release(&self, id: TaskId) {
+        self.shared.owned.release(id);
+    }
+}
+
+// ===== impl Shared =====
+
+impl Shared {
+    /// Lets an idle worker know there is a task to run. A sleeping worker is
+    /// woken if there is one, otherwise the worker parked on the reactor
+    fn notify(&self) {
+        // Pairs with the fence in `park` and `sleep`. Either they see the
+        // task, or we see that they are idle
+        atomic::fence(Ordering::SeqCst);
+
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _idle = self.idle.lock().unwrap();
+            self.condvar.notify_one();
+        } else if self.reactor_parked.load(Ordering::SeqCst) {
+            self.io.unpark();
+        }
+    }
+
+    fn has_tasks(&self) -> bool {
+        !self.injector.is_empty() || self.stealers.iter().any(|stealer| !stealer.is_empty())
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Parks on the reactor until there is IO, a timer fires or a task is
+    /// scheduled. If another worker is already parked on it, sleeps instead
+    fn park(&self) {
+        let mut reactor = match self.reactor.try_lock() {
+            Ok(reactor) => reactor,
+            Err(_) => return self.sleep(),
+        };
+
+        self.time.process();
+        let timeout = self.time.park_timeout();
+        self.reactor_parked.store(true, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+
+        if !self.has_tasks() && !self.is_shutdown() {
+            tracing::debug!("Worker parking on epoll");
+            reactor
+                .react(timeout)
+                .expect("Reactor failed to process events");
+        }
+
+        self.reactor_parked.store(false, Ordering::SeqCst);
+        self.time.unparked();
+        drop(reactor);
+        self.time.process();
+
+        // This worker goes off to run whatever was woken, so a sleeping
+        // worker takes its place on the reactor
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _idle = self.idle.lock().unwrap();
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Sleeps until notified of new tasks
+    fn sleep(&self) {
+        let idle = self.idle.lock().unwrap();
+        self.sleeping.fetch_add(1, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+
+        if !self.has_tasks() && !self.is_shutdown() {
+            tracing::debug!("Worker sleeping");
+            // Waking up spuriously is fine, the worker looks for tasks and
+            // parks again
+            let _idle = self.condvar.wait(idle).unwrap();
+        }
+
+        self.sleeping.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Fires due timers and dispatches IO events without blocking, unless
+    /// another worker is parked on the reactor
+    fn poll_reactor(&self) {
+        if let Ok(mut reactor) = self.reactor.try_lock() {
+            reactor
+                .react(Some(Duration::ZERO))
+                .expect("Reactor failed to process events");
+            drop(reactor);
+            self.time.process();
+        }
+    }
+}
+
+// ===== impl Local =====
+
+impl Local {
+    /// The worker's main loop
+    // `is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn run(self) {
+        let shared = self.shared.clone();
+        LOCAL.with(|local| *local.borrow_mut() = Some(self));
+
+        let mut tick: u32 = 0;
+        while !shared.is_shutdown() {
+            tick = tick.wrapping_add(1);
+            if tick % shared.reactor_interval == 0 {
+                shared.poll_reactor();
+            }
+
+            // The borrow is released before running the task, since waking
+            // a task schedules it onto this worker's queue
+            let task = LOCAL.with(|local| local.borrow().as_ref().and_then(|l| l.next_task(tick)));
+            match task {
+                Some(task) => {
+                    tracing::debug!("Task {}: Running on worker", task.id());
+                    task.run()
+                }
+                None => shared.park(),
+            }
+        }
+
+        LOCAL.with(|local| local.borrow_mut().take());
+    }
+
+    /// Finds the next task to run. Looks in our own queue first, then the
+    /// injection queue and then the other workers' queues
+    #[allow(clippy::manual_is_multiple_of)]
+    fn next_task(&self, tick: u32) -> Option<Task> {
+        let shared = &self.shared;
+
+        if tick % INJECTOR_INTERVAL == 0 {
+            if let Some(task) = steal(|| shared.injector.steal()) {
+                return Some(task);
+            }
+        }
+
+        self.queue.pop().or_else(|| {
+            steal(|| {
+                shared
+                    .injector
+                    .steal_batch_and_pop(&self.queue)
+                    .or_else(|| {
+                        shared
+                            .stealers
+                            .iter()
+                            .enumerate()
+                            .filter(|(index, _)| *index != self.index)
+                            .map(|(_, stealer)| stealer.steal_batch_and_pop(&self.queue))
+                            .collect()
+                    })
+            })
+        })
+    }
+}
+
+/// Retries stealing for as long as it is interrupted by a concurrent
+/// operation
+fn steal(mut f: impl FnMut() -> crossbeam::deque::Steal<Task>) -> Option<Task> {
+    iter::repeat_with(&mut f)
+        .find(|steal| !steal.is_retry())
+        .and_then(|steal| steal.success())
+}
+
+// ===== impl ThreadWaker =====
+
+impl ArcWake for ThreadWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.thread.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::net::SocketAddr;
+
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::net::{TcpListener, TcpStream};
+    use crate::time::{sleep, Instant};
+    use crate::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn spread_across_workers() {
+        let rt = Runtime::new_multi_thread(4);
+        let threads = rt.block_on(async {
+            let handles: Vec<_> = (0..16)
+                .map(|_| {
+                    crate::spawn(async {
+                        // Hogs the worker so the others pick up the rest
+                        thread::sleep(Duration::from_millis(10));
+                        thread::current().name().unwrap().to_string()
+                    })
+                })
+                .collect();
+
+            let mut threads = HashSet::new();
+            for handle in handles {
+                threads.insert(handle.await.unwrap());
+            }
+            threads
+        });
+
+        assert!(threads.len() > 1);
+        assert!(threads.iter().all(|name| name.starts_with("woi-worker-")));
+    }
+
+    #[test]
+    fn io_and_timers() {
+        let rt = Runtime::new_multi_thread(2);
+        rt.block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let server = crate::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 5];
+                stream.read_exact(&mut buf).await.unwrap();
+                stream.write_all(&buf).await.unwrap();
+            });
+
+            let client = crate::spawn(async move {
+                let start = Instant::now();
+                sleep(Duration::from_millis(20)).await;
+                assert!(start.elapsed() >= Duration::from_millis(20));
+
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream.write_all(b"hello").await.unwrap();
+                let mut buf = [0; 5];
+                stream.read_exact(&mut buf).await.unwrap();
+                buf
+            });
+
+            server.await.unwrap();
+            assert_eq!(&client.await.unwrap(), b"hello");
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "current-thread runtime")]
+    fn spawn_local_on_multi_thread() {
+        let rt = Runtime::new_multi_thread(1);
+        rt.block_on(async {
+            crate::spawn_local(async {});
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::ptr::NonNull;
+
+use crate::task::header::Header;
+
+/// A handle that can abort a task without owning its output. Unlike the
+/// [`JoinHandle`](crate::JoinHandle) it can be cloned, and dropping it
+/// leaves the task running
+pub struct AbortHandle {
+    /// Pointer to raw task
+    pub(crate) raw: NonNull<()>,
+}
+
+// Aborting only touches the task's state and scheduler, both of which can
+// be used from any thread
+unsafe impl Send for AbortHandle {}
+unsafe impl Sync for AbortHandle {}
+
+impl AbortHandle {
+    /// Takes a new reference to the task
+    pub(crate) fn new(raw: NonNull<()>) -> AbortHandle {
+        let header = raw.as_ptr() as *const Header;
+        unsafe { (*header).state.ref_incr() };
+        AbortHandle { raw }
+    }
+
+    /// Aborts the task. Its future is dropped the next time it is
+    /// scheduled and the `JoinHandle` resolves to
+    /// [`JoinError::Cancelled`](crate::task::JoinError::Cancelled). Does
+    /// nothing if the task has already completed
+    pub fn abort(&self) {
+        let raw = self.raw.as_ptr();
+        let header = raw as *const Header;
+        unsafe { ((*header).vtable.abort)(raw) }
+    }
+}
+
+impl Clone for AbortHandle {
+    fn clone(&self) -> AbortHandle {
+        AbortHandle::new(self.raw)
+    }
+}
+
+impl Drop for AbortHandle {
+    fn drop(&mut self) {
+        let raw = self.raw.as_ptr();
+        let header = raw as *const Header;
+        unsafe { ((*header).vtable.drop_reference)(raw) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::DropFlag;
+    use crate::time::sleep;
+    use crate::Runtime;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn abort_idle_task() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let dropped = Arc::new(AtomicBool::new(false));
+            let flag = DropFlag(dropped.clone());
+            let handle = crate::spawn(async move {
+                let _flag = flag;
+                sleep(Duration::from_secs(60)).await;
+            });
+
+            // Let the task start and park on its timer
+            sleep(Duration::from_millis(10)).await;
+            handle.abort();
+
+            let err = handle.await.unwrap_err();
+            assert!(err.is_cancelled());
+            assert!(!err.is_panic());
+            assert!(dropped.load(Ordering::Acquire));
+        });
+    }
+
+    #[test]
+    fn abort_from_another_thread() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let handle = crate::spawn(sleep(Duration::from_secs(60)));
+            let abort = handle.abort_handle();
+
+            thread::spawn(move || abort.clone().abort());
+            assert!(handle.await.unwrap_err().is_cancelled());
+        });
+    }
+}
@@ -0,0 +1,188 @@
+//! Cooperative scheduling
+//!
+//! A task only gives the thread back when it returns pending. One whose
+//! resources are always ready, e.g. a socket that keeps receiving data or
+//! a channel that is never empty, would never do so and starve every other
+//! task along with the reactor. To prevent that, each poll of a task gets
+//! a budget that woi's IO, channel and timer futures take from. Once it is
+//! spent they return pending, waking the task straight away so it runs
+//! again after the others had their turn.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::poll_fn;
+
+/// How many operations a task can perform per poll
+const BUDGET: u8 = 128;
+
+thread_local! {
+    /// Budget left for the task being polled, if any
+    static CURRENT: Cell<Option<u8>> = const { Cell::new(None) }
+}
+
+/// Runs the closure, usually a poll, with a fresh budget. The previous
+/// budget is restored afterwards, even if the closure panics
+pub(crate) fn budget<R>(f: impl FnOnce() -> R) -> R {
+    with_budget(Some(BUDGET), f)
+}
+
+/// Runs the closure without a budget, so nothing in it is ever told to
+/// yield. The previous budget is restored afterwards
+pub(crate) fn unconstrained<R>(f: impl FnOnce() -> R) -> R {
+    with_budget(None, f)
+}
+
+fn with_budget<R>(budget: Option<u8>, f: impl FnOnce() -> R) -> R {
+    struct Reset(Option<u8>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = CURRENT.try_with(|budget| budget.set(self.0));
+        }
+    }
+
+    let prev = CURRENT.with(|current| current.replace(budget));
+    let _reset = Reset(prev);
+    f()
+}
+
+/// Takes one operation from the budget. Once it is spent, the task is
+/// woken and pending is returned. Always ready outside of a budget, e.g.
+/// on a thread that isn't part of a runtime
+pub(crate) fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    CURRENT.with(|budget| match budget.get() {
+        Some(0) => {
+            tracing::debug!("Budget spent, yielding");
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(left) => {
+            budget.set(Some(left - 1));
+            Poll::Ready(())
+        }
+        None => Poll::Ready(()),
+    })
+}
+
+/// Yields back to the runtime once, so other tasks get to run before this
+/// one continues
+pub async fn yield_now() {
+    Yield { yielded: false }.await
+}
+
+/// Takes one operation from the task's budget, yielding back to the runtime
+/// if it is spent. Lets busy loops that don't use woi's resources, or only
+/// ones that are always ready, take part in cooperative scheduling
+pub async fn consume_budget() {
+    poll_fn(poll_proceed).await
+}
+
+/// Returns pending once, waking itself straight away
+struct Yield {
+    yielded: bool,
+}
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::mpsc::unbounded;
+    use crate::time::sleep;
+    use crate::Runtime;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn busy_task_yields() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            // The channel is never empty, so without a budget this task
+            // would never give the thread back
+            let (tx, rx) = unbounded::channel();
+            crate::spawn(async move {
+                loop {
+                    tx.send(()).unwrap();
+                    rx.recv().await;
+                }
+            });
+
+            // Needs both the other tasks and the reactor to get a turn
+            let done = Arc::new(AtomicBool::new(false));
+            let task_done = done.clone();
+            crate::spawn(async move {
+                sleep(Duration::from_millis(10)).await;
+                task_done.store(true, Ordering::Release);
+            })
+            .await
+            .unwrap();
+            assert!(done.load(Ordering::Acquire));
+        });
+    }
+
+    #[test]
+    fn yield_and_consume_budget() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let done = Arc::new(AtomicBool::new(false));
+            let task_done = done.clone();
+            crate::spawn(async move { task_done.store(true, Ordering::Release) });
+
+            yield_now().await;
+            assert!(done.load(Ordering::Acquire));
+
+            // The root future has a budget too. Once it is spent, the next
+            // operation yields
+            for _ in 0..BUDGET {
+                consume_budget().await;
+            }
+            let mut consume = Box::pin(consume_budget());
+            poll_fn(|cx| {
+                assert!(consume.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+        });
+    }
+
+    #[test]
+    fn io_retry_takes_budget_once() {
+        use crate::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+        use crate::net::UnixStream;
+
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let (mut a, mut b) = UnixStream::pair().unwrap();
+            let mut buf = [0; 8];
+            a.write_all(b"hello").await.unwrap();
+            assert_eq!(b.read(&mut buf).await.unwrap(), 5);
+
+            // The stream is still marked readable, so the read is tried,
+            // would block and goes back to wait on the reactor
+            poll_fn(|cx| {
+                budget(|| {
+                    assert!(Pin::new(&mut b).poll_read(cx, &mut buf).is_pending());
+                    assert_eq!(CURRENT.with(Cell::get), Some(BUDGET - 1));
+                });
+                Poll::Ready(())
+            })
+            .await;
+        });
+    }
+}
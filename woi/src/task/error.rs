@@ -1,8 +1,36 @@
 use core::fmt;
 use std::any::Any;
 
+/// Error returned by a [`JoinHandle`](crate::JoinHandle) when its task
+/// didn't run to completion
 pub enum JoinError {
-    Panic(Box<dyn Any + 'static>),
+    /// The task panicked. Holds the panic's payload
+    Panic(Box<dyn Any + Send + 'static>),
+    /// The task was aborted before it completed
+    Cancelled,
+}
+
+impl JoinError {
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, JoinError::Cancelled)
+    }
+
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panic(_))
+    }
+
+    /// Returns the payload of the panic, e.g. to resume it with
+    /// [`std::panic::resume_unwind`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task was cancelled rather than panicking
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        match self {
+            JoinError::Panic(panic) => panic,
+            JoinError::Cancelled => panic!("`into_panic` called on a cancelled task"),
+        }
+    }
 }
 
 impl std::error::Error for JoinError {}
@@ -11,6 +39,7 @@ impl fmt::Display for JoinError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             JoinError::Panic(_) => write!(f, "panic"),
+            JoinError::Cancelled => write!(f, "task was cancelled"),
         }
     }
 }
@@ -19,6 +48,7 @@ impl fmt::Debug for JoinError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             JoinError::Panic(_) => write!(f, "JoinError::Panic(..)"),
+            JoinError::Cancelled => write!(f, "JoinError::Cancelled"),
         }
     }
 }
@@ -1,5 +1,6 @@
-use std::cell::Cell;
+use std::cell::UnsafeCell;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::task::Waker;
 
 use crate::task::raw::TaskVTable;
@@ -7,18 +8,46 @@ use crate::task::state::State;
 
 pub(crate) struct Header {
     pub state: State,
-    pub waker: Option<Waker>,        // Why is this wrapped in UnsafeCell?
+    /// Waker of the `JoinHandle`. It is only written by the handle while
+    /// the `JOIN_WAKER` bit is unset, and only read by the task once it has
+    /// seen the bit set, so the two never access it at the same time
+    pub waker: UnsafeCell<Option<Waker>>,
     pub vtable: &'static TaskVTable, // Why &'static? Think cause they are fns
     pub id: TaskId,
 }
 
 impl Header {
-    pub fn register_waker(&mut self, waker: &Waker) {
-        self.waker = Some(waker.clone());
+    /// Registers the `JoinHandle`'s waker. Returns false if the task
+    /// completed in the meantime, in which case its output can be read
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the `JoinHandle`
+    pub unsafe fn register_join_waker(&self, waker: &Waker) -> bool {
+        let snapshot = self.state.load();
+        if snapshot.has_join_waker() {
+            // Nothing to do if the registered waker wakes the same task
+            if let Some(existing) = &*self.waker.get() {
+                if existing.will_wake(waker) {
+                    return true;
+                }
+            }
+            // The bit has to be unset before the waker can be swapped out
+            if self.state.unset_join_waker().is_err() {
+                return false;
+            }
+        }
+
+        *self.waker.get() = Some(waker.clone());
+        self.state.set_join_waker().is_ok()
     }
 
-    pub fn wake_join_handle(&self) {
-        match &self.waker {
+    /// # Safety
+    ///
+    /// Must only be called by the task once it has completed with the
+    /// `JOIN_WAKER` bit set
+    pub unsafe fn wake_join_handle(&self) {
+        match &*self.waker.get() {
             Some(waker) => waker.wake_by_ref(),
             None => panic!("Missing waker!"),
         }
@@ -27,29 +56,22 @@ impl Header {
 
 /// A monotonic counter that is updated through interior
 /// mutability. Allows it used as a static while still
-/// being able to be updated
+/// being able to be updated, from any thread
 #[derive(Default)]
-struct Counter(Cell<u64>);
+struct Counter(AtomicU64);
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct TaskId(u64);
 
 // ===== impl Counter =====
 
-// Implement sync for counter to enable it to be used as
-// a static. It is safe to do so because we aren't sharing
-// it across threads
-unsafe impl Sync for Counter {}
-
 impl Counter {
     const fn new() -> Counter {
-        Counter(Cell::new(0))
+        Counter(AtomicU64::new(0))
     }
     pub fn incr(&self) -> u64 {
-        let prev = self.0.get();
-        let new = prev + 1;
-        self.0.set(new);
-        new
+        // Ids only need to be unique, not ordered with anything else
+        self.0.fetch_add(1, Ordering::Relaxed) + 1
     }
 }
 
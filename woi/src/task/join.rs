@@ -4,6 +4,10 @@ use std::pin::Pin;
 use std::ptr::NonNull;
 use std::task::{Context, Poll};
 
+use futures::ready;
+
+use super::abort::AbortHandle;
+use super::coop;
 use crate::task::header::Header;
 
 /// A handle to the task
@@ -13,15 +17,42 @@ pub struct JoinHandle<T> {
     pub(crate) _marker: PhantomData<T>,
 }
 
+// The handle only gives access to the task's output, so it can be sent to
+// another thread as long as the output can
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+
+// The output lives in the task, never in the handle, so the handle can be
+// moved freely
+impl<T> Unpin for JoinHandle<T> {}
+
+impl<T> JoinHandle<T> {
+    /// Aborts the task. Its future is dropped the next time it is
+    /// scheduled and the handle resolves to
+    /// [`JoinError::Cancelled`](crate::task::JoinError::Cancelled). Does
+    /// nothing if the task has already completed
+    pub fn abort(&self) {
+        let raw = self.raw.as_ptr();
+        let header = raw as *const Header;
+        unsafe { ((*header).vtable.abort)(raw) }
+    }
+
+    /// Returns a handle that can abort the task from elsewhere
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle::new(self.raw)
+    }
+}
+
 impl<T> Future for JoinHandle<T> {
     type Output = super::Result<T>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(coop::poll_proceed(cx));
+
         let raw = self.raw.as_ptr();
         let mut output = Poll::Pending;
 
         unsafe {
-            let header = &mut *(raw as *mut Header);
+            let header = &*(raw as *const Header);
 
             let id = header.id;
             tracing::debug!(
@@ -30,11 +61,9 @@ impl<T> Future for JoinHandle<T> {
                 header.state.is_complete()
             );
 
-            if !header.state.is_complete() {
-                // Register waker with the task
-                header.register_waker(cx.waker());
-                header.state.set_join_waker();
-            } else {
+            // Register waker with the task. This fails if the task completed
+            // in the meantime, in which case the output is ready
+            if header.state.is_complete() || !header.register_join_waker(cx.waker()) {
                 tracing::debug!("Task {}: JoinHandle ready", id);
                 (header.vtable.get_output)(self.raw.as_ptr(), &mut output as *mut _ as *mut ());
             }
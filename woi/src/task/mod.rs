@@ -1,6 +1,13 @@
+mod abort;
+pub use abort::AbortHandle;
+
+pub(crate) mod coop;
+pub use coop::{consume_budget, yield_now};
+
 mod error;
+pub use error::JoinError;
 
-mod header;
+pub(crate) mod header;
 
 pub(crate) mod join;
 pub use join::JoinHandle;
@@ -11,9 +18,10 @@ mod result;
 pub(crate) use result::Result;
 
 mod spawn;
-pub use spawn::spawn;
+pub use spawn::{spawn, spawn_blocking, spawn_local};
 
 mod state;
 
+#[allow(clippy::module_inception)]
 mod task;
 pub(crate) use task::Task;
@@ -1,10 +1,12 @@
 use std::alloc::{self, Layout};
+use std::cell::UnsafeCell;
 use std::future::Future;
 use std::mem;
 use std::pin::Pin;
-use std::ptr::NonNull;
+use std::ptr::{self, NonNull};
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 
+use super::coop;
 use super::error::JoinError;
 use super::header::{Header, TaskId};
 use super::state::State;
@@ -47,12 +49,18 @@ pub struct TaskVTable {
     pub(crate) poll: unsafe fn(*const ()),
     pub(crate) get_output: unsafe fn(*const (), *mut ()),
     pub(crate) drop_join_handle: unsafe fn(*const ()),
+    pub(crate) abort: unsafe fn(*const ()),
+    pub(crate) drop_reference: unsafe fn(*const ()),
 }
 
 // All schedulers must implement the Schedule trait. They
 // are responsible for sending tasks to the runtime queue
 pub(crate) trait Schedule {
     fn schedule(&self, task: Task);
+
+    /// Called once the task has completed, so the runtime can stop
+    /// tracking it
+    fn release(&self, _id: TaskId) {}
 }
 
 // ===== impl RawTask =====
@@ -71,7 +79,7 @@ where
         Self::drop_waker,
     );
 
-    pub fn new(future: F, scheduler: S) -> NonNull<()> {
+    pub fn allocate(future: F, scheduler: S) -> NonNull<()> {
         let task_layout = Self::layout();
         unsafe {
             let ptr = match NonNull::new(alloc::alloc(task_layout.layout) as *mut ()) {
@@ -85,11 +93,13 @@ where
             let header = Header {
                 id,
                 state: State::new_with_id(id),
-                waker: None,
+                waker: UnsafeCell::new(None),
                 vtable: &TaskVTable {
                     poll: Self::poll,
                     get_output: Self::get_output,
                     drop_join_handle: Self::drop_join_handle,
+                    abort: Self::abort,
+                    drop_reference: Self::drop_waker,
                 },
             };
             (raw.header as *mut Header).write(header);
@@ -143,8 +153,14 @@ where
 
         tracing::debug!("Task {}: Deallocating", header.id);
 
+        // Whatever is left of the future or its output goes along with the
+        // scheduler and the join handle's waker, which may hold on to the
+        // runtime
+        ptr::drop_in_place(raw.status);
+        ptr::drop_in_place(raw.scheduler as *mut S);
+        ptr::drop_in_place(raw.header as *mut Header);
+
         let layout = Self::layout();
-        // TODO: Investigate if I need to use .drop_in_place()
         alloc::dealloc(ptr as *mut u8, layout.layout);
     }
 
@@ -152,7 +168,7 @@ where
     // Increments the number of references to the waker
     unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
+        let header = &*raw.header;
         header.state.ref_incr();
         RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE)
     }
@@ -161,9 +177,8 @@ where
     // the task is destroyed if the reference count is 0
     unsafe fn drop_waker(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
-        header.state.ref_decr();
-        if header.state.ref_count() == 0 {
+        let header = &*raw.header;
+        if header.state.ref_decr() {
             Self::dealloc(ptr)
         }
     }
@@ -172,30 +187,26 @@ where
     // One requirement here is that it must be safe
     // to call `wake` even if the task has been driven to completion
     unsafe fn wake(ptr: *const ()) {
-        let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
-        tracing::debug!("Task {}: Waking raw task", header.id);
-
-        header.state.transition_to_scheduled();
-        // We get one reference count from the caller. We schedule a task which
-        // increases our reference count by one.
-        Self::schedule(ptr);
-        // We can now drop our reference from the caller
+        Self::wake_by_ref(ptr);
+        // We can now drop the reference we got from the caller
         Self::drop_waker(ptr);
     }
 
     unsafe fn wake_by_ref(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
+        let header = &*raw.header;
         tracing::debug!("Task {}: Waking raw task by ref", header.id);
 
-        header.state.transition_to_scheduled();
-        Self::schedule(ptr);
+        // The waker may be called from another thread while the task is
+        // running or already queued. Only one of them gets to schedule it
+        if header.state.transition_to_scheduled() {
+            Self::schedule(ptr);
+        }
     }
 
     unsafe fn schedule(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
+        let header = &*raw.header;
 
         let task = Task {
             raw: NonNull::new_unchecked(ptr as *mut ()),
@@ -214,32 +225,48 @@ where
         use std::panic;
 
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
+        let header = &*raw.header;
 
         let waker = Waker::from_raw(RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE));
         let cx = &mut Context::from_waker(&waker);
 
-        header.state.transition_to_running();
+        let snapshot = header.state.transition_to_running();
 
         let status = &mut *raw.status;
-        match Self::poll_inner(status, cx) {
+        let res = if snapshot.is_cancelled() {
+            tracing::debug!("Task {}: Cancelled", header.id);
+            Self::cancel(status);
+            Poll::Ready(())
+        } else {
+            Self::poll_inner(status, cx)
+        };
+
+        match res {
             Poll::Pending => {
                 tracing::debug!("Task pending");
-                header.state.transition_to_idle();
+                // Woken while it was running, so it goes straight back on
+                // the queue
+                if header.state.transition_to_idle() {
+                    Self::schedule(ptr);
+                }
             }
             Poll::Ready(_) => {
-                header.state.transition_to_complete();
+                let snapshot = header.state.transition_to_complete();
                 // Catch a panic if waking the JoinHandle or dropping the future
                 // panics. Since the task is already completed, we're not concerned
                 // about propagating the failure up to the caller
                 let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-                    if header.state.has_join_waker() {
-                        header.wake_join_handle();
-                    } else {
-                        // Drop the future or output by replacing it with Consumed
+                    if !snapshot.has_join_handle() {
+                        // Nobody is left to read the output. Drop the future or
+                        // output by replacing it with Consumed
                         status.drop_future_or_output();
+                    } else if snapshot.has_join_waker() {
+                        header.wake_join_handle();
                     }
                 }));
+
+                let scheduler = &*raw.scheduler;
+                scheduler.release(header.id);
             }
         }
     }
@@ -262,7 +289,7 @@ where
 
         let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
             let guard = Guard { status };
-            let res = guard.status.poll(cx);
+            let res = coop::budget(|| guard.status.poll(cx));
             // Successfully polled the future. Prevent the guard's destructor from running
             mem::forget(guard);
             res
@@ -282,6 +309,18 @@ where
         Poll::Ready(())
     }
 
+    /// Drops the future of a cancelled task and stores the error for the
+    /// `JoinHandle` in its place
+    fn cancel(status: &mut Status<F>) {
+        use std::panic;
+
+        // The task is cancelled even if dropping the future panics
+        let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            status.drop_future_or_output();
+        }));
+        *status = Status::Finished(Err(JoinError::Cancelled));
+    }
+
     unsafe fn get_output(ptr: *const (), dst: *mut ()) {
         let raw = Self::from_ptr(ptr);
         let dst = dst as *mut Poll<super::Result<F::Output>>;
@@ -296,17 +335,33 @@ where
 
     unsafe fn drop_join_handle(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
-        let header = &mut *(raw.header as *mut Header);
-
-        // unset join handle bit
-        header.state.unset_join_handle();
+        let header = &*raw.header;
+
+        // unset join handle bit. If the task already completed, its output
+        // was kept around for the handle. Nobody can read it anymore so we
+        // drop it. Otherwise the task drops it when it completes
+        let prev = header.state.unset_join_handle();
+        if prev.is_complete() {
+            let status = &mut *raw.status;
+            status.drop_future_or_output();
+        }
         // drop the reference the handle was holding, possibly
         // deallocating the task
-        header.state.ref_decr();
-        if header.state.ref_count() == 0 {
+        if header.state.ref_decr() {
             Self::dealloc(ptr)
         }
     }
+
+    /// Cancels the task. It may be called from any thread, so the future
+    /// isn't dropped here but by the task the next time it runs
+    unsafe fn abort(ptr: *const ()) {
+        let raw = Self::from_ptr(ptr);
+        let header = &*raw.header;
+
+        if header.state.transition_to_cancelled() {
+            Self::schedule(ptr);
+        }
+    }
 }
 
 // ====== impl Status =====
@@ -328,3 +383,69 @@ impl<F: Future> Status<F> {
         *self = Status::Consumed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util::DropFlag;
+    use crate::Runtime;
+
+    /// Returns pending the first time it is polled so that spawned tasks
+    /// get to run before the `block_on` future continues
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn output_kept_until_join_handle_polled() {
+        let rt = Runtime::new();
+        let output = rt.block_on(async {
+            let handle = crate::spawn(async { 5 });
+            // The task completes before anyone waits on the handle
+            YieldOnce(false).await;
+            handle.await
+        });
+        assert_eq!(output.unwrap(), 5);
+    }
+
+    #[test]
+    fn output_dropped_with_join_handle() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let flag = DropFlag(dropped.clone());
+            let handle = crate::spawn(async move { flag });
+            YieldOnce(false).await;
+            assert!(!dropped.load(Ordering::SeqCst));
+
+            drop(handle);
+            assert!(dropped.load(Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn output_dropped_without_join_handle() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let flag = DropFlag(dropped.clone());
+            drop(crate::spawn(async move { flag }));
+            YieldOnce(false).await;
+            assert!(dropped.load(Ordering::SeqCst));
+        });
+    }
+}
@@ -3,7 +3,63 @@ use std::future::Future;
 use crate::runtime;
 use crate::task::join::JoinHandle;
 
-pub fn spawn<F: Future>(future: F) -> JoinHandle<F::Output> {
+/// Spawns a task onto the current runtime. On a multi-threaded runtime it
+/// may run on any of the worker threads
+///
+/// The future has to be `Send` whichever runtime it is spawned onto. This
+/// function finds the runtime through the thread it is called on, so which
+/// flavor it spawns onto is only known once the program runs and the bounds
+/// can't depend on it. Without them, a future holding an `Rc` could be
+/// stolen by another worker of a multi-threaded runtime. Futures that
+/// aren't `Send` can be spawned onto a current-thread runtime with
+/// [`spawn_local`] or [`Runtime::spawn_local`](crate::Runtime::spawn_local)
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
     let spawner = runtime::context::spawner();
     spawner.spawn(future)
 }
+
+/// Spawns a task that doesn't have to be `Send` onto the current runtime.
+/// It always runs on the thread that calls `block_on`
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or on a multi-threaded runtime
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    let spawner = runtime::context::spawner();
+    spawner.spawn_local(future)
+}
+
+/// Runs the blocking closure on the runtime's blocking pool, keeping the
+/// runtime free to run other tasks. The returned handle resolves to the
+/// closure's output, or to [`JoinError::Panic`](crate::task::JoinError) if
+/// it panics
+///
+/// The pool spawns threads as needed, up to a limit set with
+/// [`Builder::max_blocking_threads`](crate::runtime::Builder::max_blocking_threads).
+/// Beyond that, closures wait for a thread to free up
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or if the pool has no thread and
+/// can't spawn one
+pub fn spawn_blocking<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match runtime::context::blocking().spawn_task(f) {
+        Ok(join_handle) => join_handle,
+        Err(e) => panic!("could not spawn blocking thread: {}", e),
+    }
+}
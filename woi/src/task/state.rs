@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use super::header::TaskId;
 
 // The task has been scheduled onto the executor
@@ -15,9 +17,12 @@ const JOIN_HANDLE: usize = 1 << 3;
 // The waker belonging to the join handle is registered
 const JOIN_WAKER: usize = 1 << 4;
 
+// The task was aborted. Its future is dropped the next time it runs
+const CANCELLED: usize = 1 << 5;
+
 // The idea of using a state mask and ref count mask and figuring
 // out how much to shift is from Tokio
-const STATE_MASK: usize = SCHEDULED | RUNNING | COMPLETE | JOIN_HANDLE | JOIN_WAKER;
+const STATE_MASK: usize = SCHEDULED | RUNNING | COMPLETE | JOIN_HANDLE | JOIN_WAKER | CANCELLED;
 
 // The bits belonging to the ref count. These are the upper bits.
 // It is calculated by inverting the bits belonging to the
@@ -40,101 +45,118 @@ const REF_ONE: usize = 1 << REF_COUNT_SHIFT;
 //   * The internal Task
 const INITIAL_STATE: usize = (REF_ONE * 2) | SCHEDULED | JOIN_HANDLE;
 
+/// State of a task. It is shared between the task, its wakers and its
+/// `JoinHandle`, any of which may live on a different thread, so every
+/// transition is a single atomic operation
 pub(crate) struct State {
-    pub(crate) state: usize,
+    pub(crate) state: AtomicUsize,
     task_id: Option<TaskId>,
 }
 
+/// A copy of the state taken at some point in time
+#[derive(Clone, Copy)]
+pub(crate) struct Snapshot(usize);
+
 impl State {
     #[allow(unused)]
     pub fn new() -> State {
         State {
-            state: INITIAL_STATE,
+            state: AtomicUsize::new(INITIAL_STATE),
             task_id: None,
         }
     }
 
     pub fn new_with_id(task_id: TaskId) -> State {
         State {
-            state: INITIAL_STATE,
+            state: AtomicUsize::new(INITIAL_STATE),
             task_id: Some(task_id),
         }
     }
 
-    pub fn ref_incr(&mut self) {
-        self.state += REF_ONE;
+    pub fn load(&self) -> Snapshot {
+        Snapshot(self.state.load(Ordering::Acquire))
+    }
 
-        if let Some(task_id) = self.task_id {
-            tracing::debug!(
-                "Task {}: Incr ref count. Value: {}",
-                task_id,
-                self.ref_count()
-            )
-        }
+    /// Applies the transition, retrying if another thread changed the state
+    /// in the meantime. Returns the previous state, or an error if the
+    /// transition was refused
+    fn fetch_update<F>(&self, mut f: F) -> Result<Snapshot, Snapshot>
+    where
+        F: FnMut(Snapshot) -> Option<Snapshot>,
+    {
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                f(Snapshot(state)).map(|next| next.0)
+            })
+            .map(Snapshot)
+            .map_err(Snapshot)
     }
 
-    pub fn ref_decr(&mut self) {
-        self.state -= REF_ONE;
+    pub fn ref_incr(&self) {
+        let prev = self.state.fetch_add(REF_ONE, Ordering::Relaxed);
 
         if let Some(task_id) = self.task_id {
             tracing::debug!(
-                "Task {}: Decr ref count. Value: {}",
+                "Task {}: Incr ref count. Value: {}",
                 task_id,
-                self.ref_count()
+                Snapshot(prev).ref_count() + 1
             )
         }
     }
 
-    pub fn ref_count(&self) -> usize {
-        // To calculate the ref count, we AND with the ref count mask
-        // and then shift the bits down so that they begin at the
-        // start bit of the reference count
-        (self.state & REF_COUNT_MASK) >> REF_COUNT_SHIFT
-    }
+    /// Drops a reference. Returns true if it was the last one, in which case
+    /// the caller deallocates the task
+    pub fn ref_decr(&self) -> bool {
+        let prev = self.state.fetch_sub(REF_ONE, Ordering::AcqRel);
+        let ref_count = Snapshot(prev).ref_count() - 1;
 
-    pub fn unset_join_handle(&mut self) {
-        self.state &= !JOIN_HANDLE;
-    }
+        if let Some(task_id) = self.task_id {
+            tracing::debug!("Task {}: Decr ref count. Value: {}", task_id, ref_count)
+        }
 
-    pub fn set_join_waker(&mut self) {
-        self.state |= JOIN_WAKER;
+        ref_count == 0
     }
 
-    pub fn has_join_waker(&self) -> bool {
-        self.state & JOIN_WAKER == JOIN_WAKER
+    #[allow(unused)]
+    pub fn ref_count(&self) -> usize {
+        self.load().ref_count()
     }
 
     pub fn is_complete(&self) -> bool {
-        self.state & COMPLETE == COMPLETE
-    }
-
-    pub fn set_complete(&mut self) {
-        self.state |= COMPLETE;
-    }
-
-    pub fn is_scheduled(&self) -> bool {
-        self.state & SCHEDULED == SCHEDULED
+        self.load().is_complete()
     }
 
-    pub fn set_scheduled(&mut self) {
-        self.state |= SCHEDULED;
+    /// Unsets the join handle bit, returning the state from before
+    pub fn unset_join_handle(&self) -> Snapshot {
+        Snapshot(self.state.fetch_and(!JOIN_HANDLE, Ordering::AcqRel))
     }
 
-    pub fn unset_scheduled(&mut self) {
-        self.state &= !SCHEDULED;
+    /// Marks the join handle's waker as registered. Fails if the task has
+    /// completed, since it won't look at the waker anymore
+    pub fn set_join_waker(&self) -> Result<Snapshot, Snapshot> {
+        self.fetch_update(|curr| {
+            if curr.is_complete() {
+                return None;
+            }
+            Some(Snapshot(curr.0 | JOIN_WAKER))
+        })
     }
 
-    pub fn set_running(&mut self) {
-        self.state |= RUNNING;
+    /// Takes the join handle's waker back so it can be replaced. Fails if
+    /// the task has completed, since it may be reading the waker
+    pub fn unset_join_waker(&self) -> Result<Snapshot, Snapshot> {
+        self.fetch_update(|curr| {
+            if curr.is_complete() {
+                return None;
+            }
+            Some(Snapshot(curr.0 & !JOIN_WAKER))
+        })
     }
 
-    pub fn unset_running(&mut self) {
-        self.state &= !RUNNING;
-    }
-
-    pub fn transition_to_complete(&mut self) {
-        self.set_complete();
-        self.unset_running();
+    pub fn transition_to_complete(&self) -> Snapshot {
+        let prev = self.fetch_update(|curr| Some(Snapshot((curr.0 | COMPLETE) & !RUNNING)));
+        // The closure never refuses the transition
+        let prev = prev.unwrap_or_else(|prev| prev);
         if let Some(task_id) = self.task_id {
             tracing::debug!(
                 "Task {}: Transitioned to complete. State: {}",
@@ -142,50 +164,141 @@ impl State {
                 self
             );
         }
+        prev
     }
 
-    pub fn transition_to_running(&mut self) {
-        self.set_running();
-        self.unset_scheduled();
+    /// Returns the state from before, which tells the caller whether the
+    /// task was cancelled
+    pub fn transition_to_running(&self) -> Snapshot {
+        let prev = self.fetch_update(|curr| Some(Snapshot((curr.0 | RUNNING) & !SCHEDULED)));
         if let Some(task_id) = self.task_id {
             tracing::debug!("Task {}: Transitioned to running. State: {}", task_id, self);
         }
+        prev.unwrap_or_else(|prev| prev)
     }
 
-    pub fn transition_to_idle(&mut self) {
-        self.unset_running();
-        self.unset_scheduled();
+    /// Moves a task that returned pending out of the running state. Returns
+    /// true if it was woken while it was running, in which case it is left
+    /// scheduled and the caller puts it back on the queue
+    pub fn transition_to_idle(&self) -> bool {
+        let prev = self.fetch_update(|curr| Some(Snapshot(curr.0 & !RUNNING)));
+        let prev = prev.unwrap_or_else(|prev| prev);
         if let Some(task_id) = self.task_id {
             tracing::debug!("Task {}: Transitioned to idle. State: {}", task_id, self);
         }
+        prev.is_scheduled()
     }
 
-    pub fn transition_to_scheduled(&mut self) {
-        self.set_scheduled();
-        self.unset_running();
-        if let Some(task_id) = self.task_id {
-            tracing::debug!(
-                "Task {}: Transitioned to scheduled. State: {}",
-                task_id,
-                self
-            );
+    /// Marks the task as scheduled. Returns true if the caller should put it
+    /// on the queue. That is not the case if it is already queued, complete
+    /// or currently running, since it is then rescheduled once it returns
+    pub fn transition_to_scheduled(&self) -> bool {
+        let res = self.fetch_update(|curr| {
+            if curr.is_complete() || curr.is_scheduled() {
+                return None;
+            }
+            Some(Snapshot(curr.0 | SCHEDULED))
+        });
+
+        match res {
+            Ok(prev) => {
+                if let Some(task_id) = self.task_id {
+                    tracing::debug!(
+                        "Task {}: Transitioned to scheduled. State: {}",
+                        task_id,
+                        self
+                    );
+                }
+                !prev.is_running()
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Marks the task as cancelled. Returns true if the caller should put
+    /// it on the queue so it runs and drops its future. A task that is
+    /// queued or running gets there by itself, and one that has completed
+    /// or was already cancelled is left alone
+    pub fn transition_to_cancelled(&self) -> bool {
+        let res = self.fetch_update(|curr| {
+            if curr.is_complete() || curr.is_cancelled() {
+                return None;
+            }
+            // Setting the scheduled bit on a running task makes it
+            // reschedule itself if it returns pending
+            Some(Snapshot(curr.0 | CANCELLED | SCHEDULED))
+        });
+
+        match res {
+            Ok(prev) => {
+                if let Some(task_id) = self.task_id {
+                    tracing::debug!(
+                        "Task {}: Transitioned to cancelled. State: {}",
+                        task_id,
+                        self
+                    );
+                }
+                !prev.is_scheduled() && !prev.is_running()
+            }
+            Err(_) => false,
         }
     }
 }
 
 impl std::fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // scheduled | running | complete | join handle | join waker | ref count
-        let scheduled = self.is_scheduled();
-        let running = self.state & RUNNING == RUNNING;
-        let complete = self.is_complete();
-        let join_handle = self.state & JOIN_HANDLE == JOIN_HANDLE;
-        let join_waker = self.has_join_waker();
-        let ref_count = self.ref_count();
+        self.load().fmt(f)
+    }
+}
+
+// ===== impl Snapshot =====
+
+impl Snapshot {
+    pub fn ref_count(self) -> usize {
+        // To calculate the ref count, we AND with the ref count mask
+        // and then shift the bits down so that they begin at the
+        // start bit of the reference count
+        (self.0 & REF_COUNT_MASK) >> REF_COUNT_SHIFT
+    }
+
+    pub fn has_join_handle(self) -> bool {
+        self.0 & JOIN_HANDLE == JOIN_HANDLE
+    }
+
+    pub fn has_join_waker(self) -> bool {
+        self.0 & JOIN_WAKER == JOIN_WAKER
+    }
+
+    pub fn is_complete(self) -> bool {
+        self.0 & COMPLETE == COMPLETE
+    }
+
+    pub fn is_scheduled(self) -> bool {
+        self.0 & SCHEDULED == SCHEDULED
+    }
+
+    pub fn is_running(self) -> bool {
+        self.0 & RUNNING == RUNNING
+    }
+
+    pub fn is_cancelled(self) -> bool {
+        self.0 & CANCELLED == CANCELLED
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // scheduled | running | complete | join handle | join waker | cancelled | ref count
         write!(
             f,
-            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}, ref_count={} }}",
-            scheduled, running, complete, join_handle, join_waker, ref_count
+            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}, cancelled={}, ref_count={} }}",
+            self.is_scheduled(),
+            self.is_running(),
+            self.is_complete(),
+            self.has_join_handle(),
+            self.has_join_waker(),
+            self.is_cancelled(),
+            self.ref_count()
         )
     }
 }
@@ -202,15 +315,59 @@ mod tests {
 
     #[test]
     fn incr_ref_count_ok() {
-        let mut state = State::new();
+        let state = State::new();
         state.ref_incr();
         assert_eq!(state.ref_count(), 3);
     }
 
     #[test]
     fn decr_ref_count_ok() {
-        let mut state = State::new();
-        state.ref_decr();
+        let state = State::new();
+        assert!(!state.ref_decr());
         assert_eq!(state.ref_count(), 1);
     }
+
+    #[test]
+    fn unset_join_handle_ok() {
+        let state = State::new();
+        assert!(state.load().has_join_handle());
+        assert!(state.unset_join_handle().has_join_handle());
+        assert!(!state.load().has_join_handle());
+    }
+
+    #[test]
+    fn woken_while_running() {
+        let state = State::new();
+        state.transition_to_running();
+
+        // The task is rescheduled by whoever is running it, not the waker
+        assert!(!state.transition_to_scheduled());
+        assert!(!state.transition_to_scheduled());
+        assert!(state.transition_to_idle());
+
+        state.transition_to_running();
+        assert!(!state.transition_to_idle());
+        assert!(state.transition_to_scheduled());
+    }
+
+    #[test]
+    fn cancel() {
+        // Queued tasks see the bit when they next run
+        let state = State::new();
+        assert!(!state.transition_to_cancelled());
+        assert!(state.transition_to_running().is_cancelled());
+
+        // Idle tasks have to be scheduled by whoever cancelled them
+        let state = State::new();
+        state.transition_to_running();
+        state.transition_to_idle();
+        assert!(state.transition_to_cancelled());
+        assert!(!state.transition_to_cancelled());
+
+        // Running tasks reschedule themselves
+        let state = State::new();
+        state.transition_to_running();
+        assert!(!state.transition_to_cancelled());
+        assert!(state.transition_to_idle());
+    }
 }
@@ -1,12 +1,50 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::mem;
 use std::ptr::NonNull;
 
 use super::header::{Header, TaskId};
+use super::join::JoinHandle;
+use super::raw::{RawTask, Schedule};
 
 pub(crate) struct Task {
     pub(crate) raw: NonNull<()>,
 }
 
+// SAFETY: tasks are moved between the worker threads of a multi-threaded
+// runtime, which only accepts futures that are `Send`. Futures that aren't
+// are spawned with `spawn_local`, which only a current-thread runtime
+// accepts. Its tasks can still reach other threads, e.g. a waker called
+// from one puts the task on the remote queue, but those threads only touch
+// the task's state, reference count and scheduler. The future and its
+// output are only ever polled or dropped on the runtime's thread:
+//
+// * by running the task, which the runtime only does on its own thread
+// * by dropping the `JoinHandle`, which is only `Send` if the output is
+// * by shutting down, which runs every task left so that none of them
+//   still hold a future afterwards
+//
+// The local queue leaks its tasks if it is dropped on another thread
+unsafe impl Send for Task {}
+
 impl Task {
+    /// Allocates a task running the future, which is scheduled using the
+    /// scheduler whenever it is woken. Returns the task along with its
+    /// `JoinHandle`
+    pub fn new<F, S>(future: F, scheduler: S) -> (Task, JoinHandle<F::Output>)
+    where
+        F: Future,
+        S: Schedule,
+    {
+        let raw = RawTask::allocate(future, scheduler);
+        let task = Task { raw };
+        let join_handle = JoinHandle {
+            raw,
+            _marker: PhantomData,
+        };
+        (task, join_handle)
+    }
+
     pub fn id(&self) -> TaskId {
         let ptr = self.raw.as_ptr();
         let header = ptr as *const Header;
@@ -16,6 +54,19 @@ impl Task {
     pub fn run(self) {
         let ptr = self.raw.as_ptr();
         let header = ptr as *const Header;
+        // The task's reference is handed over to the poll, which drops it
+        // along with the waker it creates
+        mem::forget(self);
         unsafe { ((*header).vtable.poll)(ptr) }
     }
 }
+
+impl Drop for Task {
+    // Only tasks that never got to run end up here, e.g. ones left in a
+    // queue when the runtime shuts down
+    fn drop(&mut self) {
+        let ptr = self.raw.as_ptr();
+        let header = ptr as *const Header;
+        unsafe { ((*header).vtable.drop_reference)(ptr) }
+    }
+}
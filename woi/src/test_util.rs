@@ -0,0 +1,14 @@
+//! Helpers shared by the unit tests
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Sets the flag once dropped. Moved into a future, it tells whether the
+/// future has been dropped
+pub(crate) struct DropFlag(pub Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,134 @@
+//! The runtime's source of time
+//!
+//! Normally this is just `CLOCK_MONOTONIC`. For tests, with the `test-util`
+//! feature, a runtime can start with its clock paused using
+//! [`Builder::start_paused`](crate::runtime::Builder::start_paused). Time
+//! then only moves when it is advanced, either by hand with [`advance`] or by
+//! the runtime once every task is idle. This lets timer-heavy code be tested
+//! instantly and deterministically.
+//!
+//! Pausing is only supported on the current-thread runtime. Telling that
+//! every task is idle, and so that time may skip ahead, needs a single
+//! thread running them all.
+
+use std::sync::{Arc, Mutex};
+#[cfg(any(test, feature = "test-util"))]
+use std::time::Duration;
+
+use super::wheel::Handle;
+use super::Instant;
+use crate::runtime::context;
+
+/// Handle to the runtime's clock
+#[derive(Clone)]
+pub(crate) struct Clock {
+    /// The time the clock is stopped at while paused
+    paused: Arc<Mutex<Option<Instant>>>,
+}
+
+/// Moves paused time forward. Timers that become due fire before this
+/// completes
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or if time isn't paused
+#[cfg(any(test, feature = "test-util"))]
+pub async fn advance(duration: Duration) {
+    let clock = context::clock();
+    assert!(clock.is_paused(), "time is not paused");
+    clock.advance(duration);
+
+    // Gives the runtime a chance to fire the timers that are now due
+    crate::task::yield_now().await
+}
+
+/// The current time according to the runtime's clock, if there is one
+pub(super) fn now() -> Option<Instant> {
+    context::try_clock()?.now()
+}
+
+// ===== impl Clock =====
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            paused: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Stops the clock at the current time. From then on, [`Instant::now`]
+    /// stands still and only moves forward with [`advance`], or when the
+    /// runtime has nothing to do but wait on a timer, in which case it jumps
+    /// straight to that timer's deadline
+    pub fn pause(&self, time: &Handle) {
+        // Lining time up with the timer wheel's ticks means timers fire as
+        // soon as time is advanced to their deadline
+        let now = time.round_up(Instant::monotonic());
+        *self.paused.lock().unwrap() = Some(now);
+    }
+
+    /// The time the clock is paused at, if it is
+    fn now(&self) -> Option<Instant> {
+        *self.paused.lock().unwrap()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.now().is_some()
+    }
+
+    /// Moves paused time forward. Does nothing if the clock isn't paused
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn advance(&self, duration: Duration) {
+        if let Some(now) = self.paused.lock().unwrap().as_mut() {
+            *now += duration;
+        }
+    }
+
+    /// Moves paused time forward to the instant if it is later than the
+    /// current time
+    pub fn advance_to(&self, instant: Instant) {
+        if let Some(now) = self.paused.lock().unwrap().as_mut() {
+            *now = (*now).max(instant);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::time::sleep;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn advance_paused_time() {
+        let rt = Builder::new_current_thread()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let start = Instant::now();
+            let real_start = std::time::Instant::now();
+
+            let done = Rc::new(Cell::new(false));
+            let sleep = sleep(Duration::from_secs(10));
+            let task_done = done.clone();
+            crate::spawn_local(async move {
+                sleep.await;
+                task_done.set(true);
+            });
+
+            advance(Duration::from_secs(5)).await;
+            assert!(!done.get());
+            advance(Duration::from_secs(5)).await;
+            assert!(done.get());
+            assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+            // Nothing else to do, so the runtime skips ahead to the timer
+            crate::time::sleep(Duration::from_secs(60)).await;
+            assert_eq!(start.elapsed(), Duration::from_secs(70));
+            assert!(real_start.elapsed() < Duration::from_secs(5));
+        });
+    }
+}
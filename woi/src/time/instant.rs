@@ -0,0 +1,160 @@
+use std::fmt;
+use std::io;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::time::Duration;
+
+use super::clock;
+
+/// A point in time measured by `CLOCK_MONOTONIC`, so it is unaffected by
+/// changes to the system clock. Timers are scheduled against it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    /// Time since the clock's (unspecified) starting point
+    since_start: Duration,
+}
+
+impl Instant {
+    /// The current time. If the runtime's clock is paused, this is the
+    /// time it is paused at
+    pub fn now() -> Instant {
+        clock::now().unwrap_or_else(Instant::monotonic)
+    }
+
+    /// Reads `CLOCK_MONOTONIC`, ignoring the runtime's clock
+    pub(super) fn monotonic() -> Instant {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // This can only fail with an invalid clock or pointer
+        let res = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        assert_eq!(
+            res,
+            0,
+            "clock_gettime failed: {}",
+            io::Error::last_os_error()
+        );
+
+        Instant {
+            since_start: Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32),
+        }
+    }
+
+    /// Converts a [`std::time::Instant`]. Both use the same clock, but the
+    /// conversion goes through the current time of each so it is only
+    /// accurate to within a few nanoseconds
+    pub fn from_std(instant: std::time::Instant) -> Instant {
+        let (now, std_now) = (Instant::monotonic(), std::time::Instant::now());
+        match std_now.checked_duration_since(instant) {
+            Some(ago) => now - ago,
+            None => now + instant.duration_since(std_now),
+        }
+    }
+
+    pub fn into_std(self) -> std::time::Instant {
+        let (now, std_now) = (Instant::monotonic(), std::time::Instant::now());
+        match now.checked_duration_since(self) {
+            Some(ago) => std_now - ago,
+            None => std_now + self.duration_since(now),
+        }
+    }
+
+    /// Returns the time elapsed from `earlier` to this instant, or zero if
+    /// `earlier` is later
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.since_start.checked_sub(earlier.since_start)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.since_start.saturating_sub(earlier.since_start)
+    }
+
+    /// Time elapsed since this instant
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        let since_start = self.since_start.checked_add(duration)?;
+        Some(Instant { since_start })
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        let since_start = self.since_start.checked_sub(duration)?;
+        Some(Instant { since_start })
+    }
+
+    /// Roughly 30 years from now. Stands in for deadlines that are too far
+    /// out to represent, so they never fire in practice
+    pub(crate) fn far_future() -> Instant {
+        Instant::now() + Duration::from_secs(86400 * 365 * 30)
+    }
+
+    /// The current time plus the duration, saturating at
+    /// [`far_future`](Instant::far_future) instead of overflowing
+    pub(crate) fn after(duration: Duration) -> Instant {
+        Instant::now()
+            .checked_add(duration)
+            .unwrap_or_else(Instant::far_future)
+    }
+
+    /// The instant as a time on `CLOCK_MONOTONIC`, for use with absolute
+    /// timers
+    pub(crate) fn as_duration(&self) -> Duration {
+        self.since_start
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        self.checked_add(rhs)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        self.checked_sub(rhs)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+impl From<std::time::Instant> for Instant {
+    fn from(instant: std::time::Instant) -> Instant {
+        Instant::from_std(instant)
+    }
+}
+
+impl fmt::Debug for Instant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Instant").field(&self.since_start).finish()
+    }
+}
@@ -0,0 +1,360 @@
+use std::convert::TryFrom;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::poll_fn;
+use futures::{ready, Stream};
+
+use super::clock;
+use super::sleep::{try_sleep_until, Sleep};
+use super::timer::Timer;
+use super::Instant;
+use crate::io::epoll::Interest;
+use crate::io::io_source::Direction;
+use crate::io::pollable::Pollable;
+use crate::runtime::context;
+
+/// Stream of ticks returned from a call to [`interval`] or [`interval_at`]
+pub struct Interval {
+    driver: Driver,
+    period: Duration,
+    /// When the next tick is scheduled
+    deadline: Instant,
+    /// Ticks that are due but haven't been returned yet
+    pending: u64,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+/// What wakes an interval up when its next tick is due
+enum Driver {
+    /// A periodic timerfd, which also counts the ticks that were missed
+    Timer(Pollable<Timer>),
+    /// A sleep on the timer wheel that is reset every period. Used when
+    /// time is paused, since the kernel's clock keeps running
+    Sleep(Sleep),
+}
+
+/// What an [`Interval`] does when ticks are missed because it wasn't
+/// polled in time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Returns every missed tick straight away until it has caught up
+    #[default]
+    Burst,
+    /// Returns one tick and restarts the schedule from now, so the next
+    /// tick is a full period away
+    Delay,
+    /// Returns one tick and drops the rest, keeping the original schedule
+    Skip,
+}
+
+/// Creates an [`Interval`] that ticks once every period. The first tick
+/// completes immediately
+///
+/// # Panics
+///
+/// Panics if the period is zero or if the timer can't be created. See
+/// [`try_interval`] for a version that returns an error instead
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+/// Creates an [`Interval`] that first ticks at `start` and then once every
+/// period
+///
+/// # Panics
+///
+/// Panics if the period is zero or if the timer can't be created
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    match try_interval_at(start, period) {
+        Ok(interval) => interval,
+        Err(e) => panic!("timer error: {}", e),
+    }
+}
+
+/// Same as [`interval`] but returns an error if the timer can't be
+/// created, e.g. when the process is out of file descriptors
+///
+/// # Panics
+///
+/// Panics if the period is zero
+pub fn try_interval(period: Duration) -> io::Result<Interval> {
+    try_interval_at(Instant::now(), period)
+}
+
+/// Same as [`interval_at`] but returns an error if the timer can't be
+/// created
+///
+/// # Panics
+///
+/// Panics if the period is zero
+pub fn try_interval_at(start: Instant, period: Duration) -> io::Result<Interval> {
+    assert!(period > Duration::ZERO, "interval period must be non-zero");
+
+    // The first tick is due straight away if the start has passed. The
+    // timer then takes care of the ones after it
+    let (first, pending) = if start <= Instant::now() {
+        (periods_after(start, period, 1), 1)
+    } else {
+        (start, 0)
+    };
+
+    let driver = if clock::now().is_some() {
+        Driver::Sleep(try_sleep_until(first)?)
+    } else {
+        // Intervals run on a timerfd rather than the timer wheel, but are
+        // timers all the same
+        context::try_time()?;
+        let timer = Timer::interval_at(first, period)?;
+        Driver::Timer(Pollable::register(timer, Interest::READABLE)?)
+    };
+
+    Ok(Interval {
+        driver,
+        period,
+        deadline: start,
+        pending,
+        missed_tick_behavior: MissedTickBehavior::default(),
+    })
+}
+
+// ===== impl Interval =====
+
+impl Interval {
+    /// Completes when the next tick is due, returning when it was
+    /// scheduled
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer can't be read. See [`try_tick`](Interval::try_tick)
+    /// for a version that returns an error instead
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Same as [`tick`](Interval::tick) but returns an error if the timer
+    /// can't be read
+    pub async fn try_tick(&mut self) -> io::Result<Instant> {
+        poll_fn(|cx| self.poll_try_tick(cx)).await
+    }
+
+    /// Polls for the next tick
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer can't be read. See
+    /// [`poll_try_tick`](Interval::poll_try_tick) for a version that
+    /// returns an error instead
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        match ready!(self.poll_try_tick(cx)) {
+            Ok(tick) => Poll::Ready(tick),
+            Err(e) => panic!("timer error: {}", e),
+        }
+    }
+
+    /// Same as [`poll_tick`](Interval::poll_tick) but returns an error if
+    /// the timer can't be read
+    pub fn poll_try_tick(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Instant>> {
+        let mut missed = 0;
+
+        if self.pending == 0 {
+            let expirations = ready!(self.driver.poll_expirations(self.period, cx))?;
+
+            missed = expirations - 1;
+            self.pending = match self.missed_tick_behavior {
+                MissedTickBehavior::Burst => expirations,
+                MissedTickBehavior::Delay | MissedTickBehavior::Skip => 1,
+            };
+        }
+
+        let tick = self.deadline;
+        self.pending -= 1;
+        self.deadline = match self.missed_tick_behavior {
+            MissedTickBehavior::Burst => periods_after(tick, self.period, 1),
+            MissedTickBehavior::Skip => periods_after(tick, self.period, missed + 1),
+            MissedTickBehavior::Delay if missed > 0 => {
+                match self.driver.rearm(self.period) {
+                    Ok(()) => Instant::after(self.period),
+                    // The old schedule is still running, so we carry on
+                    // with it as if skipping
+                    Err(e) => {
+                        tracing::warn!("Interval: could not delay timer: {}", e);
+                        periods_after(tick, self.period, missed + 1)
+                    }
+                }
+            }
+            MissedTickBehavior::Delay => periods_after(tick, self.period, 1),
+        };
+
+        Poll::Ready(Ok(tick))
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
+
+// ===== impl Driver =====
+
+impl Driver {
+    /// Waits for the next tick to be due, returning how many ticks have
+    /// been due since the last call
+    fn poll_expirations(
+        &mut self,
+        period: Duration,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<u64>> {
+        match self {
+            // The timerfd counts how many times it fired since we last
+            // read it, which tells us how many ticks were missed
+            Driver::Timer(timer) => timer.poll_io(Direction::Read, cx, |timer| timer.expirations()),
+            Driver::Sleep(sleep) => {
+                ready!(Pin::new(&mut *sleep).poll(cx));
+
+                let due = sleep.deadline();
+                let late = Instant::now().saturating_duration_since(due);
+                let missed = u64::try_from(late.as_nanos() / period.as_nanos());
+                let expirations = missed.unwrap_or(u64::MAX).saturating_add(1);
+                sleep.reset(periods_after(due, period, expirations));
+                Poll::Ready(Ok(expirations))
+            }
+        }
+    }
+
+    /// Restarts the schedule so the next tick is a period from now
+    fn rearm(&mut self, period: Duration) -> io::Result<()> {
+        match self {
+            Driver::Timer(timer) => timer.get_ref().rearm(period, period),
+            Driver::Sleep(sleep) => {
+                sleep.reset(Instant::after(period));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The instant `n` periods after `instant`. Saturates instead of
+/// overflowing, e.g. when a long period was missed many times
+fn periods_after(instant: Instant, period: Duration, n: u64) -> Instant {
+    let nanos = period.as_nanos().saturating_mul(n as u128);
+    let secs = u64::try_from(nanos / 1_000_000_000).unwrap_or(u64::MAX);
+    let offset = Duration::new(secs, (nanos % 1_000_000_000) as u32);
+    instant
+        .checked_add(offset)
+        .unwrap_or_else(Instant::far_future)
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let tick = ready!(self.get_mut().poll_tick(cx));
+        Poll::Ready(Some(tick))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+    use std::thread;
+
+    const PERIOD: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn tick_every_period() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let start = Instant::now();
+            let mut interval = interval(PERIOD);
+
+            let first = interval.tick().await;
+            interval.tick().await;
+            let third = interval.tick().await;
+            assert_eq!(third - first, PERIOD * 2);
+            assert!(start.elapsed() >= PERIOD * 2);
+
+            let start = Instant::now() + PERIOD;
+            let mut interval = try_interval_at(start, PERIOD).unwrap();
+            assert_eq!(interval.try_tick().await.unwrap(), start);
+            assert!(Instant::now() >= start);
+        });
+    }
+
+    #[test]
+    fn missed_ticks() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let mut burst = interval(PERIOD);
+            let mut skip = interval(PERIOD);
+            skip.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            let burst_start = burst.tick().await;
+            let skip_start = skip.tick().await;
+
+            // Block the executor long enough to miss three ticks
+            thread::sleep(PERIOD * 3 + PERIOD / 2);
+
+            for i in 1..=3 {
+                assert_eq!(burst.tick().await - burst_start, PERIOD * i);
+            }
+
+            assert_eq!(skip.tick().await - skip_start, PERIOD);
+            assert_eq!(skip.tick().await - skip_start, PERIOD * 4);
+        });
+    }
+
+    #[test]
+    fn periods_after_saturates() {
+        let start = Instant::now();
+        assert_eq!(periods_after(start, PERIOD, 3), start + PERIOD * 3);
+
+        // Past what `Duration * u32` can hold
+        let long = Duration::from_secs(u64::MAX / 2);
+        assert!(periods_after(start, long, 3) > start + PERIOD);
+        assert!(periods_after(start, Duration::MAX, u64::MAX) > start + PERIOD);
+    }
+
+    #[test]
+    fn paused_time() {
+        const PERIOD: Duration = Duration::from_secs(60);
+
+        let rt = crate::runtime::Builder::new_current_thread()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let real_start = std::time::Instant::now();
+
+            let mut burst = interval(PERIOD);
+            let mut skip = interval(PERIOD);
+            skip.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let start = burst.tick().await;
+            skip.tick().await;
+
+            // Time skips ahead to the next tick
+            assert_eq!(burst.tick().await - start, PERIOD);
+            assert_eq!(Instant::now() - start, PERIOD);
+
+            crate::time::advance(PERIOD * 3 + PERIOD / 2).await;
+            for i in 2..=4 {
+                assert_eq!(burst.tick().await - start, PERIOD * i);
+            }
+            assert_eq!(skip.tick().await - start, PERIOD);
+            assert_eq!(skip.tick().await - start, PERIOD * 5);
+
+            assert!(real_start.elapsed() < Duration::from_secs(5));
+        });
+    }
+}
@@ -1,4 +1,19 @@
+mod clock;
+#[cfg(any(test, feature = "test-util"))]
+pub use clock::advance;
+pub(crate) use clock::Clock;
+
+mod instant;
+pub use instant::Instant;
+
+mod interval;
 mod sleep;
+mod timeout;
 mod timer;
+pub(crate) mod wheel;
 
-pub use sleep::sleep;
+pub use interval::{
+    interval, interval_at, try_interval, try_interval_at, Interval, MissedTickBehavior,
+};
+pub use sleep::{sleep, sleep_until, try_sleep, try_sleep_until, Sleep};
+pub use timeout::{timeout, timeout_at, Elapsed, Timeout, TimeoutExt};
@@ -1,24 +1,63 @@
 use std::future::Future;
+use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::ready;
 
-use super::timer::Timer;
-use crate::io::pollable::Pollable;
+use super::wheel::Handle;
+use super::Instant;
+use crate::task::coop;
 
-// Future that is returned from a call to `sleep`
+// Future that is returned from a call to `sleep` or `sleep_until`. It is
+// only added to the runtime's timer wheel once it is first polled
 pub struct Sleep {
-    inner: Pollable<Timer>,
+    deadline: Instant,
+    /// Key of the timer in the wheel, once registered
+    key: Option<usize>,
+    handle: Handle,
 }
 
 impl Sleep {
-    fn until(duration: Duration) -> Sleep {
-        // TODO: Saner error handling
-        let timer = Timer::new(duration).unwrap();
-        let inner = Pollable::new(timer).unwrap();
-        Sleep { inner }
+    fn until(deadline: Instant) -> Sleep {
+        Sleep {
+            deadline,
+            key: None,
+            handle: Handle::current(),
+        }
+    }
+
+    fn try_until(deadline: Instant) -> io::Result<Sleep> {
+        Ok(Sleep::with_handle(Handle::try_current()?, deadline))
+    }
+
+    /// Sleeps on the given timer wheel. Works even if timers are disabled
+    /// on the runtime, which only keeps its tasks from creating them
+    pub(crate) fn with_handle(handle: Handle, deadline: Instant) -> Sleep {
+        Sleep {
+            deadline,
+            key: None,
+            handle,
+        }
+    }
+
+    /// The instant at which the sleep completes
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves the deadline, reusing the existing timer. This works whether
+    /// or not the sleep has completed
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+        if let Some(key) = self.key {
+            self.handle.reset(key, deadline);
+        }
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
     }
 }
 
@@ -26,14 +65,95 @@ impl Future for Sleep {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // TODO: Improve error handling
-        match ready!(self.inner.poll_readable(cx)) {
-            Ok(()) => Poll::Ready(()),
-            Err(e) => panic!("timer error: {}", e),
+        ready!(coop::poll_proceed(cx));
+        let this = self.get_mut();
+
+        match this.key {
+            Some(key) if this.handle.poll_fired(key, cx.waker()) => Poll::Ready(()),
+            Some(_) => Poll::Pending,
+            None if this.is_elapsed() => Poll::Ready(()),
+            None => {
+                let key = this.handle.insert(this.deadline, cx.waker().clone());
+                this.key = Some(key);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.handle.remove(key);
         }
     }
 }
 
+/// Sleeps for the duration. One too long to represent, such as
+/// `Duration::MAX`, sleeps for practically ever
 pub fn sleep(duration: Duration) -> Sleep {
-    Sleep::until(duration)
+    Sleep::until(Instant::after(duration))
+}
+
+/// Sleeps until the deadline. Completes straight away if it has passed
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::until(deadline)
+}
+
+/// Same as [`sleep`] but returns an error instead of panicking when the
+/// timer can't be created, e.g. outside of a runtime
+pub fn try_sleep(duration: Duration) -> io::Result<Sleep> {
+    Sleep::try_until(Instant::after(duration))
+}
+
+/// Same as [`sleep_until`] but returns an error instead of panicking when
+/// the timer can't be created
+pub fn try_sleep_until(deadline: Instant) -> io::Result<Sleep> {
+    Sleep::try_until(deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::timeout;
+    use crate::Runtime;
+
+    #[test]
+    fn reset_deadline() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let start = Instant::now();
+            let mut sleep = sleep(Duration::from_secs(5));
+
+            // Registers the timer, then pulls it in
+            assert!(timeout(Duration::from_millis(10), &mut sleep)
+                .await
+                .is_err());
+            sleep.reset(start + Duration::from_millis(20));
+            assert_eq!(sleep.deadline(), start + Duration::from_millis(20));
+            (&mut sleep).await;
+            assert!(start.elapsed() >= Duration::from_millis(20));
+
+            // Pushing it out again after it completed
+            sleep.reset(Instant::now() + Duration::from_millis(10));
+            assert!(!sleep.is_elapsed());
+            (&mut sleep).await;
+            assert!(sleep.is_elapsed());
+        });
+    }
+
+    #[test]
+    fn sleep_max_duration() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let res = timeout(Duration::from_millis(10), sleep(Duration::MAX)).await;
+            assert!(res.is_err());
+            assert!(try_sleep(Duration::MAX).is_ok());
+        });
+    }
+
+    #[test]
+    fn try_sleep_outside_runtime() {
+        assert!(try_sleep(Duration::from_millis(10)).is_err());
+    }
 }
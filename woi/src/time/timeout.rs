@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::sleep::{sleep_until, Sleep};
+use super::wheel::Handle;
+use super::Instant;
+use crate::task::coop;
+
+/// Future returned from a call to [`timeout`] or [`timeout_at`]
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+/// Error returned by [`Timeout`] when the deadline passed before the future
+/// completed
+#[derive(Debug, PartialEq, Eq)]
+pub struct Elapsed(());
+
+/// Extension trait for bounding how long a future may run, e.g.
+/// `stream.read(&mut buf).timeout(duration).await`
+pub trait TimeoutExt: Future + Sized {
+    /// Same as [`timeout`]
+    fn timeout(self, duration: Duration) -> Timeout<Self> {
+        timeout(duration, self)
+    }
+
+    /// Same as [`timeout_at`]
+    fn timeout_at(self, deadline: Instant) -> Timeout<Self> {
+        timeout_at(deadline, self)
+    }
+}
+
+impl<F: Future> TimeoutExt for F {}
+
+/// Runs the future until it completes or the duration has elapsed,
+/// whichever comes first. The future is dropped if it times out. A
+/// duration too long to represent, such as `Duration::MAX`, never elapses
+/// in practice
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    timeout_at(Instant::after(duration), future)
+}
+
+/// Runs the future until it completes or the deadline is reached, whichever
+/// comes first. The future is dropped if it times out
+pub fn timeout_at<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep_until(deadline),
+    }
+}
+
+// ===== impl Timeout =====
+
+impl<F> Timeout<F> {
+    /// Same as [`timeout_at`] but with the deadline set on the given timer
+    /// wheel, even if timers are disabled on the runtime
+    pub(crate) fn with_handle(handle: Handle, deadline: Instant, future: F) -> Timeout<F> {
+        Timeout {
+            future,
+            sleep: Sleep::with_handle(handle, deadline),
+        }
+    }
+
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: the future is never moved out of the pinned `Timeout`.
+        // `Sleep` is Unpin so it doesn't need to stay pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        // The future gets polled first so one that is already complete
+        // isn't reported as timed out
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        // The future may have spent the task's budget, which would keep the
+        // sleep from ever completing. The deadline is checked regardless
+        match coop::unconstrained(|| Pin::new(&mut this.sleep).poll(cx)) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed(()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+// ===== impl Elapsed =====
+
+impl Error for Elapsed {}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::sleep;
+    use crate::Runtime;
+
+    #[test]
+    fn completes_in_time() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let res = timeout(Duration::from_secs(5), async { 1 }).await;
+            assert_eq!(res, Ok(1));
+        });
+    }
+
+    #[test]
+    fn elapses() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let slow = sleep(Duration::from_secs(5));
+            let res = slow.timeout(Duration::from_millis(10)).await;
+            assert_eq!(res, Err(Elapsed(())));
+
+            let past = Instant::now() - Duration::from_secs(1);
+            let res = futures::future::pending::<()>().timeout_at(past).await;
+            assert_eq!(res, Err(Elapsed(())));
+        });
+    }
+
+    #[test]
+    fn max_duration() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let res = timeout(Duration::MAX, async { 1 }).await;
+            assert_eq!(res, Ok(1));
+
+            let res = sleep(Duration::from_millis(10))
+                .timeout(Duration::MAX)
+                .await;
+            assert_eq!(res, Ok(()));
+        });
+    }
+
+    #[test]
+    fn elapses_with_budget_spent() {
+        let rt = Runtime::new();
+        rt.block_on(async {
+            let busy = async {
+                loop {
+                    crate::task::consume_budget().await;
+                }
+            };
+            let res = busy.timeout(Duration::from_millis(10)).await;
+            assert_eq!(res, Err(Elapsed(())));
+        });
+    }
+}
@@ -1,29 +1,48 @@
 use std::io;
-use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::time::Duration;
 
 use self::timerfd::IntervalTimerSpec;
+use super::Instant;
 
-// Does not support intervals
+/// A periodic timer backed by a timerfd. One-off timers live in the
+/// runtime's timer wheel instead. The timerfd is closed on drop
 pub(super) struct Timer {
-    fd: RawFd,
+    fd: OwnedFd,
 }
 
 impl Timer {
-    /// Creates a new timer that fires after the length of
-    /// duration has elapsed
-    pub fn new(duration: Duration) -> io::Result<Timer> {
+    /// Creates a new timer that first fires at `start` and then every
+    /// `period`
+    pub fn interval_at(start: Instant, period: Duration) -> io::Result<Timer> {
         let fd = timerfd::create()?;
-        let spec = IntervalTimerSpec::new(duration);
-        timerfd::set(fd, spec)?;
+        let timer = Timer {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+
+        // Instants are measured on the same clock as the timer, so the
+        // start can be given as an absolute time
+        let spec = IntervalTimerSpec::with_interval(start.as_duration(), period);
+        timerfd::set(fd, libc::TFD_TIMER_ABSTIME, spec)?;
+        Ok(timer)
+    }
+
+    /// Restarts the timer's schedule. It first fires after `start` has
+    /// elapsed and then every `period`
+    pub fn rearm(&self, start: Duration, period: Duration) -> io::Result<()> {
+        let spec = IntervalTimerSpec::with_interval(start, period);
+        timerfd::set(self.as_raw_fd(), 0, spec)
+    }
 
-        Ok(Timer { fd })
+    /// Returns the number of times the timer fired since the last call
+    pub fn expirations(&self) -> io::Result<u64> {
+        timerfd::read(self.as_raw_fd())
     }
 }
 
 impl AsRawFd for Timer {
     fn as_raw_fd(&self) -> RawFd {
-        self.fd
+        self.fd.as_raw_fd()
     }
 }
 
@@ -50,43 +69,65 @@ mod timerfd {
     }
 
     impl IntervalTimerSpec {
-        pub fn new(duration: Duration) -> IntervalTimerSpec {
-            let interval = TimeSpec { sec: 0, nanosec: 0 };
+        /// Fires after `duration` and then every `interval`. An interval
+        /// of zero fires only once
+        pub fn with_interval(duration: Duration, interval: Duration) -> IntervalTimerSpec {
+            IntervalTimerSpec {
+                interval: TimeSpec::from(interval),
+                value: TimeSpec::from(duration),
+            }
+        }
+    }
 
-            let value = TimeSpec {
+    impl From<Duration> for TimeSpec {
+        fn from(duration: Duration) -> TimeSpec {
+            TimeSpec {
                 sec: duration.as_secs() as i64,
                 nanosec: duration.subsec_nanos() as i64,
-            };
-
-            IntervalTimerSpec { interval, value }
+            }
         }
     }
 
     pub(super) fn create() -> io::Result<RawFd> {
         let flags = libc::TFD_NONBLOCK | libc::TFD_CLOEXEC;
-        cvt(unsafe { libc::timerfd_create(libc::CLOCK_REALTIME, flags) })
+        // Monotonic so that changes to the system clock don't make timers
+        // fire early or late. CLOCK_BOOTTIME isn't offered yet: it would also
+        // need Instants and the wheel's epoll timeouts to count suspended time
+        cvt(unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags) })
     }
 
-    pub(super) fn set(fd: RawFd, spec: IntervalTimerSpec) -> io::Result<()> {
+    pub(super) fn set(fd: RawFd, flags: i32, spec: IntervalTimerSpec) -> io::Result<()> {
         let spec = &spec as *const _ as *const libc::itimerspec;
-        let _ = cvt(unsafe { libc::timerfd_settime(fd, 0, spec, ptr::null_mut()) });
+        cvt(unsafe { libc::timerfd_settime(fd, flags, spec, ptr::null_mut()) })?;
         Ok(())
     }
 
+    /// Reads the number of expirations since the last read. Fails with
+    /// `WouldBlock` if the timer hasn't fired. Reads interrupted by a
+    /// signal are retried
+    pub(super) fn read(fd: RawFd) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        loop {
+            let res = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut _, buf.len()) };
+            if res >= 0 {
+                return Ok(u64::from_ne_bytes(buf));
+            }
+
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
     #[allow(unused)]
     pub(super) fn get(fd: RawFd) -> io::Result<IntervalTimerSpec> {
         let mut spec = IntervalTimerSpec::default();
         let spec_ptr = &mut spec as *mut _ as *mut libc::itimerspec;
-        let _ = cvt(unsafe { libc::timerfd_gettime(fd, spec_ptr) });
+        cvt(unsafe { libc::timerfd_gettime(fd, spec_ptr) })?;
         Ok(spec)
     }
 
-    #[allow(unused)]
-    pub(super) fn close(fd: RawFd) -> io::Result<()> {
-        cvt(unsafe { libc::close(fd) })?;
-        Ok(())
-    }
-
     // Converts C error codes into a Rust Result type
     fn cvt(result: i32) -> io::Result<i32> {
         if result < 0 {
@@ -104,19 +145,18 @@ mod tests {
     #[test]
     fn create_timer() {
         let duration = Duration::from_secs(3);
-        let timer = Timer::new(duration);
+        let timer = Timer::interval_at(Instant::now() + duration, duration);
         assert!(timer.is_ok());
-        let _ = timerfd::close(timer.unwrap().fd);
     }
 
     #[test]
     fn set_get_timer_ok() {
         let duration = Duration::from_secs(3);
-        let timer = Timer::new(duration);
+        let timer = Timer::interval_at(Instant::now() + duration, duration);
         assert!(timer.is_ok());
 
         let timer = timer.unwrap();
-        let spec = timerfd::get(timer.fd).unwrap();
+        let spec = timerfd::get(timer.as_raw_fd()).unwrap();
         let sec = spec.value.sec;
         let nanosec = spec.value.nanosec;
         let remaining_duration = Duration::new(sec as u64, nanosec as u32);
@@ -125,7 +165,42 @@ mod tests {
         // test will evaluate within a time period that this is still 2 (instead of lower)
         assert_eq!(2, sec);
         assert!(remaining_duration < duration);
+    }
+
+    fn open_fds() -> usize {
+        std::fs::read_dir("/proc/self/fd").unwrap().count()
+    }
+
+    #[test]
+    fn close_fds_on_drop() {
+        use crate::time::interval;
+        use crate::Runtime;
+        use std::process::Command;
+
+        // Other tests open fds of their own while this one runs, so the
+        // count is only exact in a process running nothing but this test
+        const CHILD: &str = "WOI_CLOSE_FDS_CHILD";
+        if std::env::var_os(CHILD).is_none() {
+            let status = Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "time::timer::tests::close_fds_on_drop"])
+                .args(["--test-threads", "1"])
+                .env(CHILD, "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let before = open_fds();
+
+        // Each run opens an epoll instance and a timerfd
+        for _ in 0..8 {
+            let rt = Runtime::new();
+            rt.block_on(async {
+                interval(Duration::from_secs(1)).tick().await;
+            });
+        }
 
-        let _ = timerfd::close(timer.fd);
+        assert_eq!(open_fds(), before);
     }
 }
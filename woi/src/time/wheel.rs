@@ -0,0 +1,368 @@
+//! Hashed timer wheel
+//!
+//! Time is split into ticks of one millisecond. Each timer is hashed into
+//! one of [`NUM_SLOTS`] slots by its deadline tick, so a slot holds timers
+//! for every tick that maps onto it, possibly several rotations apart. The
+//! runtime computes the nearest deadline and passes it as the timeout when
+//! parking on the reactor, then fires every timer that has expired once it
+//! wakes up. No file descriptors or syscalls are involved.
+//!
+//! On a multi-threaded runtime, timers are added by the workers while
+//! another thread may be parked on the reactor. If a timer is due before
+//! the parked thread would wake up, the reactor is unparked so the thread
+//! can recompute its timeout.
+
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::Waker;
+use std::time::Duration;
+
+use slab::Slab;
+
+use super::Instant;
+use crate::io::reactor::Handle as IoHandle;
+
+/// Number of slots in the wheel. Must be a power of two
+const NUM_SLOTS: usize = 512;
+
+/// Maps a tick onto its slot
+const SLOT_MASK: u64 = NUM_SLOTS as u64 - 1;
+
+pub(crate) struct Wheel {
+    /// Point in time that ticks are counted from
+    start: Instant,
+    /// Last tick that was processed. Every timer with a deadline at or
+    /// before it has fired
+    elapsed: u64,
+    /// Keys of the timers hashed into each slot
+    slots: Vec<Vec<usize>>,
+    /// Every timer in the wheel
+    entries: Slab<Entry>,
+    /// Tick that the thread parked on the reactor wakes up at, if there is
+    /// one. `u64::MAX` if it is parked without a timeout
+    parked_until: Option<u64>,
+}
+
+/// Handle to the timer wheel
+#[derive(Clone)]
+pub(crate) struct Handle {
+    inner: Arc<Mutex<Wheel>>,
+    /// Used to wake the thread parked on the reactor when a timer is due
+    /// before it would wake up
+    io: IoHandle,
+}
+
+struct Entry {
+    /// Tick at which the timer fires
+    deadline: u64,
+    /// Waker of the task waiting on the timer
+    waker: Option<Waker>,
+    /// Whether the timer has fired. Fired timers are no longer in a slot
+    fired: bool,
+}
+
+// ===== impl Wheel =====
+
+impl Wheel {
+    pub fn new() -> Wheel {
+        Wheel {
+            start: Instant::now(),
+            elapsed: 0,
+            slots: vec![Vec::new(); NUM_SLOTS],
+            entries: Slab::new(),
+            parked_until: None,
+        }
+    }
+
+    /// Adds a timer firing at the deadline. Returns the key identifying it
+    fn insert(&mut self, deadline: Instant, waker: Waker) -> usize {
+        // Ticks that were already processed won't be looked at again, so
+        // timers that are due go in the next one
+        let deadline = self.ticks_ceil(deadline).max(self.elapsed + 1);
+        let key = self.entries.insert(Entry {
+            deadline,
+            waker: Some(waker),
+            fired: false,
+        });
+        self.slots[slot(deadline)].push(key);
+        key
+    }
+
+    /// Returns whether the timer has fired, storing the waker if it hasn't
+    fn poll_fired(&mut self, key: usize, waker: &Waker) -> bool {
+        let entry = &mut self.entries[key];
+        if entry.fired {
+            return true;
+        }
+
+        match &mut entry.waker {
+            Some(existing) if existing.will_wake(waker) => {}
+            slot => *slot = Some(waker.clone()),
+        }
+        false
+    }
+
+    /// Moves the timer to a new deadline. It can fire again if it already
+    /// had
+    fn reset(&mut self, key: usize, deadline: Instant) {
+        let entry = &self.entries[key];
+        if !entry.fired {
+            self.unlink(key, entry.deadline);
+        }
+
+        let deadline = self.ticks_ceil(deadline).max(self.elapsed + 1);
+        let entry = &mut self.entries[key];
+        entry.deadline = deadline;
+        entry.fired = false;
+        self.slots[slot(deadline)].push(key);
+    }
+
+    fn remove(&mut self, key: usize) {
+        let entry = self.entries.remove(key);
+        if !entry.fired {
+            self.unlink(key, entry.deadline);
+        }
+    }
+
+    /// Removes the key from the slot it was hashed into
+    fn unlink(&mut self, key: usize, deadline: u64) {
+        let slot = &mut self.slots[slot(deadline)];
+        if let Some(pos) = slot.iter().position(|k| *k == key) {
+            slot.swap_remove(pos);
+        }
+    }
+
+    /// Fires every timer whose deadline has passed, returning the wakers
+    /// of the tasks waiting on them
+    pub fn process(&mut self, now: Instant) -> Vec<Waker> {
+        let now = self.ticks_floor(now);
+        let mut wakers = Vec::new();
+        if now <= self.elapsed {
+            return wakers;
+        }
+
+        // After a full rotation every slot has been visited
+        let ticks = (now - self.elapsed).min(NUM_SLOTS as u64);
+        for tick in self.elapsed + 1..=self.elapsed + ticks {
+            let Wheel { slots, entries, .. } = self;
+            slots[slot(tick)].retain(|key| {
+                let entry = &mut entries[*key];
+                if entry.deadline > now {
+                    return true;
+                }
+
+                entry.fired = true;
+                wakers.extend(entry.waker.take());
+                false
+            });
+        }
+
+        self.elapsed = now;
+        wakers
+    }
+
+    /// Records that a thread is about to park on the reactor and returns
+    /// how long it should wait for
+    fn park(&mut self, now: Instant) -> Option<Duration> {
+        let deadline = self.next_deadline();
+        self.parked_until = Some(deadline.unwrap_or(u64::MAX));
+        let deadline = self.start + Duration::from_millis(deadline?);
+        Some(deadline.saturating_duration_since(now))
+    }
+
+    /// Whether the parked thread has to be unparked to fire the timer in
+    /// time. It is then considered unparked, so this is true only once
+    fn needs_unpark(&mut self, key: usize) -> bool {
+        let deadline = self.entries[key].deadline;
+        match self.parked_until {
+            Some(parked_until) if deadline < parked_until => {
+                self.parked_until = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// How long until the next timer fires. This is used as the timeout
+    /// when parking on the reactor. Returns `None` if there are no timers
+    pub fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        let deadline = self.next_deadline_instant()?;
+        Some(deadline.saturating_duration_since(now))
+    }
+
+    /// The instant the next timer fires at
+    pub fn next_deadline_instant(&self) -> Option<Instant> {
+        let deadline = self.next_deadline()?;
+        Some(self.start + Duration::from_millis(deadline))
+    }
+
+    /// Finds the nearest deadline tick
+    fn next_deadline(&self) -> Option<u64> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        // Timers firing within the next rotation are found by walking the
+        // slots in order. Anything further out is rare enough to scan for
+        for tick in self.elapsed + 1..=self.elapsed + NUM_SLOTS as u64 {
+            let slot = &self.slots[slot(tick)];
+            if slot.iter().any(|key| self.entries[*key].deadline == tick) {
+                return Some(tick);
+            }
+        }
+
+        self.entries
+            .iter()
+            .filter(|(_, entry)| !entry.fired)
+            .map(|(_, entry)| entry.deadline)
+            .min()
+    }
+
+    /// Converts an instant into ticks since the start, rounding up so
+    /// timers never fire early
+    fn ticks_ceil(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.start);
+        since.as_nanos().div_ceil(1_000_000) as u64
+    }
+
+    /// Converts an instant into ticks since the start, rounding down
+    fn ticks_floor(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.start);
+        since.as_millis() as u64
+    }
+}
+
+fn slot(tick: u64) -> usize {
+    (tick & SLOT_MASK) as usize
+}
+
+// ===== impl Handle =====
+
+impl Handle {
+    pub fn new(io: IoHandle) -> Handle {
+        Handle {
+            inner: Arc::new(Mutex::new(Wheel::new())),
+            io,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Wheel> {
+        // Wakers are never called with the lock held, so it can't be
+        // poisoned by a panicking task
+        self.inner.lock().unwrap()
+    }
+
+    pub fn current() -> Handle {
+        crate::runtime::context::time()
+    }
+
+    pub fn try_current() -> io::Result<Handle> {
+        crate::runtime::context::try_time()
+    }
+
+    pub fn insert(&self, deadline: Instant, waker: Waker) -> usize {
+        let mut wheel = self.lock();
+        let key = wheel.insert(deadline, waker);
+        if wheel.needs_unpark(key) {
+            self.io.unpark();
+        }
+        key
+    }
+
+    pub fn poll_fired(&self, key: usize, waker: &Waker) -> bool {
+        self.lock().poll_fired(key, waker)
+    }
+
+    pub fn reset(&self, key: usize, deadline: Instant) {
+        let mut wheel = self.lock();
+        wheel.reset(key, deadline);
+        if wheel.needs_unpark(key) {
+            self.io.unpark();
+        }
+    }
+
+    pub fn remove(&self, key: usize) {
+        self.lock().remove(key)
+    }
+
+    /// Fires expired timers and wakes their tasks
+    pub fn process(&self) {
+        // The lock is released before waking in case a waker touches
+        // the wheel
+        let wakers = self.lock().process(Instant::now());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn next_timeout(&self) -> Option<Duration> {
+        self.lock().next_timeout(Instant::now())
+    }
+
+    /// Same as [`next_timeout`](Self::next_timeout), but also records that
+    /// the calling thread parks on the reactor for that long. Timers added
+    /// in the meantime that are due earlier unpark it
+    pub fn park_timeout(&self) -> Option<Duration> {
+        self.lock().park(Instant::now())
+    }
+
+    /// Records that the thread parked on the reactor has woken up
+    pub fn unparked(&self) {
+        self.lock().parked_until = None;
+    }
+
+    /// Rounds the instant up to the start of the next tick
+    pub fn round_up(&self, instant: Instant) -> Instant {
+        let wheel = self.lock();
+        wheel.start + Duration::from_millis(wheel.ticks_ceil(instant))
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.lock().next_deadline_instant()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    #[test]
+    fn fire_in_order() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let ms = Duration::from_millis;
+
+        let a = wheel.insert(start + ms(5), noop_waker());
+        // Hashes into the same slot as `a` but is a rotation further out
+        let b = wheel.insert(start + ms(5 + NUM_SLOTS as u64), noop_waker());
+        let c = wheel.insert(start + ms(3), noop_waker());
+        assert_eq!(wheel.next_timeout(start), Some(ms(3)));
+
+        assert_eq!(wheel.process(start + ms(4)).len(), 1);
+        assert!(wheel.poll_fired(c, &noop_waker()));
+        assert!(!wheel.poll_fired(a, &noop_waker()));
+        assert_eq!(wheel.next_timeout(start + ms(4)), Some(ms(1)));
+
+        assert_eq!(wheel.process(start + ms(6)).len(), 1);
+        assert!(wheel.poll_fired(a, &noop_waker()));
+        assert!(!wheel.poll_fired(b, &noop_waker()));
+        let next = ms(5 + NUM_SLOTS as u64);
+        assert_eq!(wheel.next_timeout(start), Some(next));
+
+        // Jumping more than a rotation ahead still fires it
+        assert_eq!(wheel.process(start + ms(5000)).len(), 1);
+        assert!(wheel.poll_fired(b, &noop_waker()));
+    }
+
+    #[test]
+    fn remove_timer() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+
+        let key = wheel.insert(start + Duration::from_millis(5), noop_waker());
+        wheel.remove(key);
+        assert_eq!(wheel.next_timeout(start), None);
+        assert!(wheel.process(start + Duration::from_millis(10)).is_empty());
+    }
+}